@@ -0,0 +1,60 @@
+use std::time::Instant;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use short_lease_map::ShortLeaseMap;
+
+/// The linear scan `ShortLeaseMap::insert` used before vacant slots were kept on a free list.
+struct ScanMap<T>(Vec<Option<(T, Instant)>>);
+
+impl<T> ScanMap<T> {
+    fn insert(&mut self, t: T) -> usize {
+        let idx = self.0.iter_mut().enumerate().find(|(_, v)| v.is_none());
+        match idx {
+            None => {
+                let idx = self.0.len();
+                self.0.push(Some((t, Instant::now())));
+                idx
+            }
+            Some((i, v)) => {
+                *v = Some((t, Instant::now()));
+                i
+            }
+        }
+    }
+
+    fn remove(&mut self, idx: usize) -> Option<T> {
+        self.0.get_mut(idx).and_then(Option::take).map(|o| o.0)
+    }
+}
+
+/// Fills a map with `size` entries, then repeatedly frees one from the back half and inserts a
+/// replacement, which is the steady state of a busy request table.
+fn churn(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert_after_remove");
+    for size in [100, 1_000, 10_000, 50_000] {
+        group.bench_with_input(BenchmarkId::new("free_list", size), &size, |b, &size| {
+            let mut map = ShortLeaseMap::with_capacity(size);
+            let mut keys = (0..size).map(|i| map.insert(i)).collect::<Vec<_>>();
+            let mut victim = size / 2;
+            b.iter(|| {
+                map.remove(keys[victim]);
+                keys[victim] = map.insert(black_box(victim));
+                victim = size / 2 + (victim + 1) % (size / 2);
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("scan", size), &size, |b, &size| {
+            let mut map = ScanMap(Vec::with_capacity(size));
+            let mut keys = (0..size).map(|i| map.insert(i)).collect::<Vec<_>>();
+            let mut victim = size / 2;
+            b.iter(|| {
+                map.remove(keys[victim]);
+                keys[victim] = map.insert(black_box(victim));
+                victim = size / 2 + (victim + 1) % (size / 2);
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, churn);
+criterion_main!(benches);
@@ -0,0 +1,133 @@
+use std::marker::PhantomData;
+
+use crate::{AllocationStrategy, Clock, Key, LeaseKey, ShortLeaseMap, SystemClock};
+
+/// Configures a [`ShortLeaseMap`] before building it.
+///
+/// ```
+/// use short_lease_map::{Builder, ShortLeaseMap};
+///
+/// let map: ShortLeaseMap<&str> = Builder::new().capacity(64).sliding(true).build();
+/// ```
+#[derive(Clone, Debug)]
+pub struct Builder<K = Key, C: Clock = SystemClock> {
+    pub(crate) capacity: usize,
+    pub(crate) clock: C,
+    pub(crate) sliding: bool,
+    pub(crate) tombstones: bool,
+    pub(crate) allocation: AllocationStrategy,
+    pub(crate) quarantine_time: Option<C::Duration>,
+    pub(crate) quarantine_allocations: u64,
+    pub(crate) max_slots: Option<usize>,
+    pub(crate) _key: PhantomData<fn() -> K>,
+}
+
+impl Builder {
+    /// Starts building a map with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::with_clock(SystemClock)
+    }
+}
+
+impl<C: Clock> Builder<Key, C> {
+    /// Starts building a map with the default settings, which reads the time from `clock`
+    /// instead of the system clock.
+    ///
+    /// The clock is chosen up front because settings such as
+    /// [`quarantine_for`](Self::quarantine_for) are measured by it.
+    pub fn with_clock(clock: C) -> Self {
+        Self {
+            capacity: 0,
+            clock,
+            sliding: false,
+            tombstones: false,
+            allocation: AllocationStrategy::default(),
+            quarantine_time: None,
+            quarantine_allocations: 0,
+            max_slots: None,
+            _key: PhantomData,
+        }
+    }
+}
+
+impl<K: LeaseKey, C: Clock> Builder<K, C> {
+    /// Reserves space for `size` entries up front.
+    pub fn capacity(mut self, size: usize) -> Self {
+        self.capacity = size;
+        self
+    }
+
+    /// Hands out keys of type `K2` instead of [`Key`], such as one declared with
+    /// [`new_key_type!`](crate::new_key_type).
+    pub fn keys<K2: LeaseKey>(self) -> Builder<K2, C> {
+        Builder {
+            capacity: self.capacity,
+            clock: self.clock,
+            sliding: self.sliding,
+            tombstones: self.tombstones,
+            allocation: self.allocation,
+            quarantine_time: self.quarantine_time,
+            quarantine_allocations: self.quarantine_allocations,
+            max_slots: self.max_slots,
+            _key: PhantomData,
+        }
+    }
+
+    /// When enabled, [`ShortLeaseMap::get_mut`] renews the lease of the value it returns, so values
+    /// that are still being worked on don't expire. Disabled by default.
+    pub fn sliding(mut self, sliding: bool) -> Self {
+        self.sliding = sliding;
+        self
+    }
+
+    /// When enabled, vacated slots remember whether their value was removed or expired, and when,
+    /// for [`ShortLeaseMap::lookup`] to report. Disabled by default.
+    pub fn tombstones(mut self, tombstones: bool) -> Self {
+        self.tombstones = tombstones;
+        self
+    }
+
+    /// Chooses which vacant slot gets reused when inserting. Defaults to
+    /// [`AllocationStrategy::Lifo`].
+    pub fn allocation(mut self, strategy: AllocationStrategy) -> Self {
+        self.allocation = strategy;
+        self
+    }
+
+    /// Keeps vacated slots out of use until `time` has passed, so a key that was just freed isn't
+    /// handed out again while responses to it may still be in flight. Disabled by default.
+    pub fn quarantine_for(mut self, time: C::Duration) -> Self {
+        self.quarantine_time = Some(time);
+        self
+    }
+
+    /// Keeps vacated slots out of use until `count` other values have been inserted. Disabled by
+    /// default.
+    ///
+    /// When combined with [`quarantine_for`](Self::quarantine_for), a slot is only reused once
+    /// both have been satisfied.
+    pub fn quarantine_allocations(mut self, count: u64) -> Self {
+        self.quarantine_allocations = count;
+        self
+    }
+
+    /// Caps the map at `max` slots, occupied or not, so it can't grow without bound. Once every
+    /// slot is in use, [`ShortLeaseMap::try_insert`] hands values back, and
+    /// [`ShortLeaseMap::insert_evicting`] evicts one to make room. Unlimited by default, apart
+    /// from the number of slots the key type can index.
+    pub fn max_slots(mut self, max: usize) -> Self {
+        self.max_slots = Some(max);
+        self
+    }
+
+    /// Builds the map.
+    pub fn build<T>(self) -> ShortLeaseMap<T, K, C> {
+        ShortLeaseMap::from_builder(self)
+    }
+}
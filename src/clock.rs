@@ -0,0 +1,169 @@
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// A source of time for a [`ShortLeaseMap`](crate::ShortLeaseMap).
+///
+/// Leases are stamped with [`now`](Self::now) when they are handed out, and their age is the
+/// distance between that stamp and a later call to `now`.
+pub trait Clock {
+    /// A point in time. Later instants must compare greater than earlier ones.
+    type Instant: Copy + Ord + Debug;
+    /// The distance between two instants.
+    type Duration: Copy + Ord + Debug;
+
+    /// The shortest distance between two different instants.
+    const RESOLUTION: Self::Duration;
+
+    /// The current time. Must never go backwards.
+    fn now(&self) -> Self::Instant;
+
+    /// How much time passed between `earlier` and `later`, or zero if `earlier` is actually later.
+    fn duration_since(later: Self::Instant, earlier: Self::Instant) -> Self::Duration;
+
+    /// The instant `duration` after `instant`, or `None` if that can't be represented.
+    fn checked_add(instant: Self::Instant, duration: Self::Duration) -> Option<Self::Instant>;
+
+    /// The instant `duration` before `instant`, or `None` if that can't be represented.
+    fn checked_sub(instant: Self::Instant, duration: Self::Duration) -> Option<Self::Instant>;
+}
+
+/// The default clock, reading the time from [`Instant::now`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    type Instant = Instant;
+    type Duration = Duration;
+
+    const RESOLUTION: Duration = Duration::from_nanos(1);
+
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn duration_since(later: Instant, earlier: Instant) -> Duration {
+        later.saturating_duration_since(earlier)
+    }
+
+    fn checked_add(instant: Instant, duration: Duration) -> Option<Instant> {
+        instant.checked_add(duration)
+    }
+
+    fn checked_sub(instant: Instant, duration: Duration) -> Option<Instant> {
+        instant.checked_sub(duration)
+    }
+}
+
+/// A clock that only moves when told to, for deterministic tests of expiry behaviour.
+///
+/// Clones share the same time, so a test can keep one and hand another to the map.
+#[derive(Clone, Debug)]
+pub struct ManualClock(Arc<Mutex<Instant>>);
+
+impl ManualClock {
+    /// Creates a clock stopped at the current time.
+    pub fn new() -> Self {
+        Self(Arc::new(Mutex::new(Instant::now())))
+    }
+
+    /// Moves this clock, and all of its clones, forward by `by`.
+    pub fn advance(&self, by: Duration) {
+        *self.0.lock().unwrap() += by;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    type Instant = Instant;
+    type Duration = Duration;
+
+    const RESOLUTION: Duration = Duration::from_nanos(1);
+
+    fn now(&self) -> Instant {
+        *self.0.lock().unwrap()
+    }
+
+    fn duration_since(later: Instant, earlier: Instant) -> Duration {
+        SystemClock::duration_since(later, earlier)
+    }
+
+    fn checked_add(instant: Instant, duration: Duration) -> Option<Instant> {
+        SystemClock::checked_add(instant, duration)
+    }
+
+    fn checked_sub(instant: Instant, duration: Duration) -> Option<Instant> {
+        SystemClock::checked_sub(instant, duration)
+    }
+}
+
+/// A logical clock counting caller supplied ticks, such as frames of a game loop, instead of
+/// wall clock time. Lease ages are then measured in ticks, which keeps expiry deterministic for
+/// lockstep simulations and replays.
+///
+/// The map owns its clock, so move it along with
+/// [`ShortLeaseMap::advance_ticks`](crate::ShortLeaseMap::advance_ticks) or
+/// [`ShortLeaseMap::set_tick`](crate::ShortLeaseMap::set_tick).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TickClock(u64);
+
+impl TickClock {
+    /// Creates a clock starting at `tick`.
+    pub fn new(tick: u64) -> Self {
+        Self(tick)
+    }
+
+    /// The current tick.
+    pub fn tick(self) -> u64 {
+        self.0
+    }
+
+    /// Moves the clock forward by `ticks`.
+    pub fn advance(&mut self, ticks: u64) {
+        self.0 = self.0.saturating_add(ticks);
+    }
+
+    /// Moves the clock to `tick`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tick` is earlier than the current tick, since clocks must never go backwards.
+    pub fn set(&mut self, tick: u64) {
+        assert!(
+            tick >= self.0,
+            "TickClock can't go back from {} to {tick}",
+            self.0
+        );
+        self.0 = tick;
+    }
+}
+
+impl Clock for TickClock {
+    type Instant = u64;
+    type Duration = u64;
+
+    const RESOLUTION: u64 = 1;
+
+    fn now(&self) -> u64 {
+        self.0
+    }
+
+    fn duration_since(later: u64, earlier: u64) -> u64 {
+        later.saturating_sub(earlier)
+    }
+
+    fn checked_add(instant: u64, duration: u64) -> Option<u64> {
+        instant.checked_add(duration)
+    }
+
+    fn checked_sub(instant: u64, duration: u64) -> Option<u64> {
+        instant.checked_sub(duration)
+    }
+}
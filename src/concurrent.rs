@@ -0,0 +1,395 @@
+#[cfg(loom)]
+use loom::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex, MutexGuard,
+};
+#[cfg(not(loom))]
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex, MutexGuard,
+};
+
+use crate::{expect_room, CapacityError, Clock, Key, ShortLeaseMap, SystemClock};
+
+/// A [`ShortLeaseMap`] which can be shared between threads.
+///
+/// Slots are spread across independently locked shards, so threads working on different shards
+/// never wait on each other. Inserts go to each shard in turn, and the key records which shard
+/// the value went to.
+#[derive(Debug)]
+pub struct ConcurrentShortLeaseMap<T, C: Clock = SystemClock> {
+    shards: Box<[Mutex<ShortLeaseMap<T, Key, C>>]>,
+    next_shard: AtomicUsize,
+}
+
+/// The key handed out by [`ConcurrentShortLeaseMap::insert`], naming the shard a value lives in
+/// and its [`Key`] within that shard.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ShardedKey {
+    shard: usize,
+    key: Key,
+}
+
+impl ShardedKey {
+    /// Rebuilds a key from its parts, such as after receiving them over the wire.
+    pub fn from_parts(shard: usize, key: Key) -> Self {
+        Self { shard, key }
+    }
+
+    /// The shard the value lives in.
+    pub fn shard(self) -> usize {
+        self.shard
+    }
+
+    /// The value's key within its shard.
+    pub fn key(self) -> Key {
+        self.key
+    }
+}
+
+impl<T> ConcurrentShortLeaseMap<T> {
+    /// Creates a new map with four shards for each thread the system can run in parallel.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new map with `shards` shards.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is zero.
+    pub fn with_shards(shards: usize) -> Self {
+        Self::with_shards_and_clock(shards, SystemClock)
+    }
+}
+
+impl<T, C: Clock + Clone> ConcurrentShortLeaseMap<T, C> {
+    /// Creates a new map with `shards` shards, each reading the time from a clone of `clock`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is zero.
+    pub fn with_shards_and_clock(shards: usize, clock: C) -> Self {
+        assert!(
+            shards > 0,
+            "ConcurrentShortLeaseMap needs at least one shard"
+        );
+        Self {
+            shards: (0..shards)
+                .map(|_| Mutex::new(ShortLeaseMap::with_clock(clock.clone())))
+                .collect(),
+            next_shard: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl<T, C: Clock> ConcurrentShortLeaseMap<T, C> {
+    /// How many shards the slots are spread across.
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Adds a value to the map. See [`ShortLeaseMap::insert`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the shard whose turn it is is full. Use [`try_insert`](Self::try_insert) to
+    /// handle that instead.
+    pub fn insert(&self, t: T) -> ShardedKey {
+        expect_room(self.try_insert(t))
+    }
+
+    /// Adds a value to the map, but if the shard whose turn it is is full, hands the value back
+    /// instead of panicking. See [`ShortLeaseMap::try_insert`].
+    pub fn try_insert(&self, t: T) -> Result<ShardedKey, CapacityError<T>> {
+        self.insert_with(|map| map.try_insert(t))
+    }
+
+    /// Adds a value to the map which expires once `ttl` has passed. See
+    /// [`ShortLeaseMap::insert_with_ttl`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the shard whose turn it is is full.
+    pub fn insert_with_ttl(&self, t: T, ttl: C::Duration) -> ShardedKey {
+        expect_room(self.insert_with(|map| map.try_insert_with_ttl(t, ttl)))
+    }
+
+    /// Claims a slot before its value exists, locking its shard only while reserving and filling
+    /// it. See [`ShortLeaseMap::reserve`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the shard whose turn it is is [full](ShortLeaseMap::try_insert). Use
+    /// [`try_reserve`](Self::try_reserve) to handle that instead.
+    pub fn reserve(&self) -> ShardedVacantLease<'_, T, C> {
+        self.try_reserve().expect("ShortLeaseMap is full")
+    }
+
+    /// Claims a slot like [`reserve`](Self::reserve), but returns `None` if the shard whose turn
+    /// it is is full.
+    pub fn try_reserve(&self) -> Option<ShardedVacantLease<'_, T, C>> {
+        let shard = self.next_shard();
+        let key = {
+            let mut map = self.shards[shard].lock().unwrap();
+            let index = map.reserve_slot()?;
+            map.key_at(index)
+        };
+        Some(ShardedVacantLease {
+            map: self,
+            key: ShardedKey::from_parts(shard, key),
+            armed: true,
+        })
+    }
+
+    /// Clones the value for this key. Returns `None` if the key is stale.
+    pub fn get(&self, key: ShardedKey) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.with(key, T::clone)
+    }
+
+    /// Calls `f` with a reference to the value for this key while its shard is locked. Returns
+    /// `None` if the key is stale.
+    pub fn with<R>(&self, key: ShardedKey, f: impl FnOnce(&T) -> R) -> Option<R> {
+        self.lock(key.shard)?.get(key.key).map(f)
+    }
+
+    /// Calls `f` with a mutable reference to the value for this key while its shard is locked.
+    /// Returns `None` if the key is stale.
+    pub fn with_mut<R>(&self, key: ShardedKey, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        self.lock(key.shard)?.get_mut(key.key).map(f)
+    }
+
+    /// Removes the value with this key. See [`ShortLeaseMap::remove`].
+    pub fn remove(&self, key: ShardedKey) -> Option<T> {
+        self.lock(key.shard)?.remove(key.key)
+    }
+
+    /// Renews the lease for this key. See [`ShortLeaseMap::renew`].
+    pub fn renew(&self, key: ShardedKey) -> bool {
+        self.lock(key.shard)
+            .is_some_and(|mut map| map.renew(key.key))
+    }
+
+    /// Evicts values older than `max_age` from every shard, locking one shard at a time. Returns a
+    /// count of how many items were removed. See [`ShortLeaseMap::dump_old_values`].
+    pub fn dump_old_values(&self, max_age: C::Duration) -> usize {
+        let mut total_dumped = 0;
+        self.for_each_shard(|_, map| total_dumped += map.dump_old_values(max_age));
+        total_dumped
+    }
+
+    /// Evicts values whose deadline has been reached from every shard, locking one shard at a
+    /// time. Returns a count of how many items were removed. See
+    /// [`ShortLeaseMap::dump_expired`].
+    pub fn dump_expired(&self) -> usize {
+        let mut total_dumped = 0;
+        self.for_each_shard(|_, map| total_dumped += map.dump_expired());
+        total_dumped
+    }
+
+    /// Like [`dump_old_values`](Self::dump_old_values), but hands back each evicted value along
+    /// with its key and the time it was inserted.
+    pub fn drain_expired(&self, max_age: C::Duration) -> Vec<(ShardedKey, T, C::Instant)> {
+        let mut drained = Vec::new();
+        self.for_each_shard(|shard, map| {
+            drained.extend(
+                map.drain_expired(max_age)
+                    .map(|(key, t, at)| (ShardedKey::from_parts(shard, key), t, at)),
+            )
+        });
+        drained
+    }
+
+    /// Like [`dump_expired`](Self::dump_expired), but hands back each evicted value along with its
+    /// key and the time it was inserted.
+    pub fn drain_overdue(&self) -> Vec<(ShardedKey, T, C::Instant)> {
+        let mut drained = Vec::new();
+        self.for_each_shard(|shard, map| {
+            drained.extend(
+                map.drain_overdue()
+                    .map(|(key, t, at)| (ShardedKey::from_parts(shard, key), t, at)),
+            )
+        });
+        drained
+    }
+
+    /// Inserts with `f` into the shard whose turn it is. The shard is unlocked again by the time
+    /// this returns, so callers can panic without poisoning it.
+    fn insert_with(
+        &self,
+        f: impl FnOnce(&mut ShortLeaseMap<T, Key, C>) -> Result<Key, CapacityError<T>>,
+    ) -> Result<ShardedKey, CapacityError<T>> {
+        let shard = self.next_shard();
+        let key = f(&mut self.shards[shard].lock().unwrap())?;
+        Ok(ShardedKey::from_parts(shard, key))
+    }
+
+    /// The shard the next insert goes to.
+    fn next_shard(&self) -> usize {
+        self.next_shard.fetch_add(1, Ordering::Relaxed) % self.shards.len()
+    }
+
+    fn lock(&self, shard: usize) -> Option<MutexGuard<'_, ShortLeaseMap<T, Key, C>>> {
+        self.shards.get(shard).map(|s| s.lock().unwrap())
+    }
+
+    /// Calls `f` on each shard in turn, holding only that shard's lock.
+    fn for_each_shard(&self, mut f: impl FnMut(usize, &mut ShortLeaseMap<T, Key, C>)) {
+        for (i, shard) in self.shards.iter().enumerate() {
+            f(i, &mut shard.lock().unwrap());
+        }
+    }
+}
+
+/// A slot claimed by [`ConcurrentShortLeaseMap::reserve`] which doesn't have a value yet. Its
+/// shard is only locked while reserving and filling the slot.
+///
+/// Dropping the lease without [filling](Self::fill) it releases the slot, and the key becomes
+/// stale, as if its value had been removed.
+#[derive(Debug)]
+pub struct ShardedVacantLease<'a, T, C: Clock = SystemClock> {
+    map: &'a ConcurrentShortLeaseMap<T, C>,
+    key: ShardedKey,
+    /// Whether dropping the lease releases the slot.
+    armed: bool,
+}
+
+impl<T, C: Clock> ShardedVacantLease<'_, T, C> {
+    /// The key the value will have once the lease is filled.
+    pub fn key(&self) -> ShardedKey {
+        self.key
+    }
+
+    /// Puts the value in the reserved slot, returning its key. The lease starts now, not when the
+    /// slot was reserved.
+    pub fn fill(mut self, t: T) -> ShardedKey {
+        self.armed = false;
+        self.fill_with(t, None)
+    }
+
+    /// Puts the value in the reserved slot like [`fill`](Self::fill), with a value which expires
+    /// once `ttl` has passed.
+    pub fn fill_with_ttl(mut self, t: T, ttl: C::Duration) -> ShardedKey {
+        self.armed = false;
+        self.fill_with(t, Some(ttl))
+    }
+
+    fn fill_with(&self, t: T, ttl: Option<C::Duration>) -> ShardedKey {
+        let ShardedKey { shard, key } = self.key;
+        self.map.shards[shard]
+            .lock()
+            .unwrap()
+            .fill_reserved(key.index(), t, ttl);
+        self.key
+    }
+}
+
+impl<T, C: Clock> Drop for ShardedVacantLease<'_, T, C> {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        if let Ok(mut map) = self.map.shards[self.key.shard].lock() {
+            map.release_reserved(self.key.key.index());
+        }
+    }
+}
+
+impl<T> Default for ConcurrentShortLeaseMap<T> {
+    fn default() -> Self {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Self::with_shards(threads * 4)
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use std::{sync::Arc, thread, time::Duration};
+
+    use super::*;
+    use crate::{Builder, ManualClock};
+
+    #[test]
+    fn spreads_across_shards() {
+        let map = ConcurrentShortLeaseMap::with_shards(2);
+        let a = map.insert("a");
+        let b = map.insert("b");
+        assert_ne!(a.shard(), b.shard());
+        assert_eq!(map.get(a), Some("a"));
+        assert_eq!(map.with_mut(b, |b| std::mem::replace(b, "c")), Some("b"));
+        assert_eq!(map.remove(b), Some("c"));
+        assert_eq!(map.get(b), None);
+        assert_eq!(map.get(ShardedKey::from_parts(7, a.key())), None);
+    }
+
+    #[test]
+    fn reserve() {
+        let map = ConcurrentShortLeaseMap::with_shards(1);
+        let lease = map.reserve();
+        let key = lease.key();
+        let other = map.insert((key, "other"));
+        assert_ne!(other, key);
+        assert_eq!(lease.fill((key, "filled")), key);
+        assert_eq!(map.get(key), Some((key, "filled")));
+
+        let abandoned = map.reserve().key();
+        assert_eq!(map.get(abandoned), None);
+        assert_eq!(map.reserve().key().key().index(), abandoned.key().index());
+
+        let map = ConcurrentShortLeaseMap {
+            shards: Box::new([Mutex::new(Builder::new().max_slots(1).build())]),
+            next_shard: AtomicUsize::new(0),
+        };
+        map.insert(());
+        assert!(map.try_insert(()).is_err());
+        assert!(std::panic::catch_unwind(|| map.insert(())).is_err());
+        assert!(map.try_reserve().is_none());
+        assert!(std::panic::catch_unwind(|| map.reserve()).is_err());
+        assert!(!map.shards[0].is_poisoned());
+    }
+
+    #[test]
+    fn sweeps_every_shard() {
+        let clock = ManualClock::new();
+        let map = ConcurrentShortLeaseMap::with_shards_and_clock(3, clock.clone());
+        let old = (0..6).map(|i| map.insert(i)).collect::<Vec<_>>();
+        clock.advance(Duration::from_secs(2));
+        let young = map.insert_with_ttl(6, Duration::from_secs(1));
+        let mut drained = map.drain_expired(Duration::from_secs(1));
+        drained.sort();
+        let drained_keys = drained.iter().map(|&(k, _, _)| k).collect::<Vec<_>>();
+        let mut old_sorted = old.clone();
+        old_sorted.sort();
+        assert_eq!(drained_keys, old_sorted);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(map.dump_expired(), 1);
+        assert_eq!(map.get(young), None);
+    }
+
+    #[test]
+    fn threads() {
+        let map = Arc::new(ConcurrentShortLeaseMap::new());
+        let handles = (0..4)
+            .map(|t| {
+                let map = Arc::clone(&map);
+                thread::spawn(move || {
+                    (0..100)
+                        .map(|i| map.insert(t * 100 + i))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        let keys = handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect::<Vec<_>>();
+        for (t, chunk) in keys.chunks(100).enumerate() {
+            for (i, &key) in chunk.iter().enumerate() {
+                assert_eq!(map.remove(key), Some(t * 100 + i));
+            }
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    error::Error,
+    fmt,
+    sync::mpsc::{self, Receiver, Sender},
+};
+
+use crate::{expect_room, Builder, CapacityError, Clock, Key, Lookup, ShortLeaseMap, SystemClock};
+
+/// Matches responses to outstanding requests by key.
+///
+/// [`register`](Self::register) a request to get a key to send along with it, and a receiver
+/// for the response. When the response arrives, [`complete`](Self::complete) it with the key it
+/// came back with. Requests still outstanding after the timeout are failed with [`TimedOut`] by
+/// [`expire`](Self::expire).
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use short_lease_map::Correlator;
+///
+/// let mut correlator = Correlator::new(Duration::from_secs(5));
+/// let (key, response) = correlator.register("GET /");
+/// // ... send the request along with the key, and later receive a response carrying it ...
+/// assert_eq!(correlator.complete(key, 200), Ok("GET /"));
+/// assert_eq!(response.recv().unwrap(), Ok(200));
+/// ```
+#[derive(Debug)]
+pub struct Correlator<Req, Resp, C: Clock = SystemClock> {
+    pending: ShortLeaseMap<Pending<Req, Resp>, Key, C>,
+    timeout: C::Duration,
+}
+
+/// Where a registered request's response, or its timeout, is delivered.
+type Response<Resp> = Receiver<Result<Resp, TimedOut>>;
+
+#[derive(Debug)]
+struct Pending<Req, Resp> {
+    request: Req,
+    reply: Sender<Result<Resp, TimedOut>>,
+}
+
+/// The error delivered to a waiting caller when no response arrived in time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimedOut;
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("no response arrived before the timeout")
+    }
+}
+
+impl Error for TimedOut {}
+
+/// Why a response could not be matched to a request. Either way the response is handed back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompleteError<Resp> {
+    /// The key was handed out, but its request is no longer outstanding. Usually this means the
+    /// response arrived after the request timed out, or it is a duplicate.
+    Late(Resp),
+    /// The key was never handed out by this correlator.
+    Unknown(Resp),
+}
+
+impl<Resp> CompleteError<Resp> {
+    /// The response that could not be matched.
+    pub fn into_response(self) -> Resp {
+        match self {
+            Self::Late(resp) | Self::Unknown(resp) => resp,
+        }
+    }
+}
+
+impl<Resp> fmt::Display for CompleteError<Resp> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Late(_) => f.write_str("response arrived for a request no longer outstanding"),
+            Self::Unknown(_) => f.write_str("response arrived for a request never made"),
+        }
+    }
+}
+
+impl<Resp: fmt::Debug> Error for CompleteError<Resp> {}
+
+impl<Req, Resp> Correlator<Req, Resp> {
+    /// Creates a correlator which fails requests that have been outstanding longer than
+    /// `timeout`.
+    pub fn new(timeout: std::time::Duration) -> Self {
+        Self::with_builder(Builder::new(), timeout)
+    }
+}
+
+impl<Req, Resp, C: Clock> Correlator<Req, Resp, C> {
+    /// Creates a correlator which keeps its outstanding requests in a map built by `builder`, for
+    /// choosing a clock or other settings.
+    pub fn with_builder(builder: Builder<Key, C>, timeout: C::Duration) -> Self {
+        Self {
+            pending: builder.build(),
+            timeout,
+        }
+    }
+
+    /// Records an outstanding request, returning the key to send along with it and a receiver for
+    /// its response. `request` is kept until the request completes or times out, and handed back
+    /// then.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the correlator's map is [full](ShortLeaseMap::try_insert). Use
+    /// [`try_register`](Self::try_register) to handle that instead.
+    pub fn register(&mut self, request: Req) -> (Key, Response<Resp>) {
+        expect_room(self.try_register(request))
+    }
+
+    /// Records an outstanding request like [`register`](Self::register), but if too many requests
+    /// are outstanding for the map's [`max_slots`](Builder::max_slots), hands the request back
+    /// instead of panicking.
+    pub fn try_register(
+        &mut self,
+        request: Req,
+    ) -> Result<(Key, Response<Resp>), CapacityError<Req>> {
+        let (reply, response) = mpsc::channel();
+        match self
+            .pending
+            .try_insert_with_ttl(Pending { request, reply }, self.timeout)
+        {
+            Ok(key) => Ok((key, response)),
+            Err(e) => Err(CapacityError(e.into_inner().request)),
+        }
+    }
+
+    /// Delivers the response for this key to whoever is waiting on it, returning the request it
+    /// answers. The request counts as answered even if nobody is waiting anymore.
+    pub fn complete(&mut self, key: Key, response: Resp) -> Result<Req, CompleteError<Resp>> {
+        match self.pending.remove(key) {
+            Some(pending) => {
+                // The caller may have given up waiting, which is no concern of ours.
+                let _ = pending.reply.send(Ok(response));
+                Ok(pending.request)
+            }
+            None => match self.pending.lookup(key) {
+                Lookup::NeverIssued => Err(CompleteError::Unknown(response)),
+                _ => Err(CompleteError::Late(response)),
+            },
+        }
+    }
+
+    /// Stops waiting for a response to this request without delivering anything, so its
+    /// receiver is disconnected. Returns the request, unless it is no longer outstanding.
+    pub fn cancel(&mut self, key: Key) -> Option<Req> {
+        self.pending.remove(key).map(|p| p.request)
+    }
+
+    /// Fails every request that has been outstanding longer than the timeout with [`TimedOut`],
+    /// returning their keys and requests.
+    pub fn expire(&mut self) -> Vec<(Key, Req)> {
+        self.pending
+            .drain_overdue()
+            .map(|(key, pending, _)| {
+                let _ = pending.reply.send(Err(TimedOut));
+                (key, pending.request)
+            })
+            .collect()
+    }
+
+    /// When the next request will time out, so a caller can sleep until then before calling
+    /// [`expire`](Self::expire).
+    pub fn next_timeout(&self) -> Option<C::Instant> {
+        self.pending.next_deadline()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TickClock;
+
+    #[test]
+    fn timeouts_and_late_responses() {
+        let mut correlator = Correlator::with_builder(Builder::with_clock(TickClock::new(0)), 5);
+        let (slow, slow_response) = correlator.register("slow");
+        let (gone, gone_response) = correlator.register("gone");
+        drop(gone_response);
+        assert_eq!(correlator.next_timeout(), Some(5));
+        assert_eq!(correlator.complete(gone, "ok"), Ok("gone"));
+        assert_eq!(
+            correlator.complete(gone, "again"),
+            Err(CompleteError::Late("again"))
+        );
+
+        correlator.pending.advance_ticks(5);
+        assert_eq!(correlator.expire(), vec![(slow, "slow")]);
+        assert_eq!(slow_response.recv().unwrap(), Err(TimedOut));
+        assert_eq!(
+            correlator.complete(slow, "finally"),
+            Err(CompleteError::Late("finally"))
+        );
+        assert_eq!(
+            correlator.complete(Key::from_parts(7, 0), "garbage"),
+            Err(CompleteError::Unknown("garbage"))
+        );
+        let (reused, _) = correlator.register("reused");
+        assert_eq!(reused.index(), slow.index());
+        assert_eq!(
+            correlator.complete(Key::from_parts(reused.index(), 9), "garbage"),
+            Err(CompleteError::Unknown("garbage"))
+        );
+    }
+
+    #[test]
+    fn cancel() {
+        let mut correlator = Correlator::<_, ()>::new(std::time::Duration::from_secs(5));
+        let (key, response) = correlator.register(1);
+        assert_eq!(correlator.cancel(key), Some(1));
+        assert!(response.recv().is_err());
+        assert!(correlator.expire().is_empty());
+    }
+
+    #[test]
+    fn full() {
+        let mut correlator = Correlator::<_, ()>::with_builder(
+            Builder::new().max_slots(1),
+            std::time::Duration::from_secs(5),
+        );
+        let (key, _response) = correlator.register(1);
+        assert_eq!(correlator.try_register(2).unwrap_err().into_inner(), 2);
+        correlator.cancel(key);
+        assert!(correlator.try_register(3).is_ok());
+    }
+}
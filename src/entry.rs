@@ -0,0 +1,103 @@
+use crate::{Clock, Departure, Key, LeaseKey, ShortLeaseMap, SystemClock};
+
+/// A slot in a [`ShortLeaseMap`], found by [`ShortLeaseMap::entry`].
+#[derive(Debug)]
+pub enum Entry<'a, T, K: LeaseKey = Key, C: Clock = SystemClock> {
+    /// The key is current, and its value is in the map.
+    Occupied(OccupiedEntry<'a, T, K, C>),
+    /// The key's slot is vacant.
+    Vacant(VacantEntry<'a, T, K, C>),
+}
+
+/// A slot holding the value for a current key.
+#[derive(Debug)]
+pub struct OccupiedEntry<'a, T, K: LeaseKey = Key, C: Clock = SystemClock> {
+    map: &'a mut ShortLeaseMap<T, K, C>,
+    index: usize,
+}
+
+impl<'a, T, K: LeaseKey, C: Clock> OccupiedEntry<'a, T, K, C> {
+    pub(crate) fn new(map: &'a mut ShortLeaseMap<T, K, C>, index: usize) -> Self {
+        Self { map, index }
+    }
+
+    /// The key of the value in this slot.
+    pub fn key(&self) -> K {
+        self.map.key_at(self.index)
+    }
+
+    /// The value in this slot.
+    pub fn get(&self) -> &T {
+        &self.map.occupant(self.index).value
+    }
+
+    /// The value in this slot, mutably. If the map was built with
+    /// [`sliding`](crate::Builder::sliding) leases, this also renews the lease, as
+    /// [`ShortLeaseMap::get_mut`] does.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.slide();
+        &mut self.map.occupant_mut(self.index).value
+    }
+
+    /// Turns the entry into a mutable reference to its value, which lives as long as the borrow
+    /// of the map. Renews the lease in the same way as [`get_mut`](Self::get_mut).
+    pub fn into_mut(mut self) -> &'a mut T {
+        self.slide();
+        &mut self.map.occupant_mut(self.index).value
+    }
+
+    /// Replaces the value in this slot, returning the old value. The lease is left as it was.
+    pub fn replace(&mut self, t: T) -> T {
+        std::mem::replace(&mut self.map.occupant_mut(self.index).value, t)
+    }
+
+    /// Resets the lease as if the value was inserted just now. See [`ShortLeaseMap::renew`].
+    pub fn renew(&mut self) {
+        let key = self.key();
+        self.map.renew(key);
+    }
+
+    /// Removes the value from the map and returns it.
+    pub fn remove(self) -> T {
+        self.map.vacate(self.index, Departure::Removed).value
+    }
+
+    fn slide(&mut self) {
+        if self.map.sliding {
+            let key = self.key();
+            self.map.renew(key);
+        }
+    }
+}
+
+/// A vacant slot. Stale keys find these once their value is gone, until the slot is reused.
+#[derive(Debug)]
+pub struct VacantEntry<'a, T, K: LeaseKey = Key, C: Clock = SystemClock> {
+    map: &'a mut ShortLeaseMap<T, K, C>,
+    index: usize,
+}
+
+impl<'a, T, K: LeaseKey, C: Clock> VacantEntry<'a, T, K, C> {
+    pub(crate) fn new(map: &'a mut ShortLeaseMap<T, K, C>, index: usize) -> Self {
+        Self { map, index }
+    }
+
+    /// The key a value inserted into this slot will get. Unless the key type has no generation,
+    /// this differs from the stale key used to find the slot.
+    pub fn key(&self) -> K {
+        self.map.key_at(self.index)
+    }
+
+    /// Puts a value in this particular slot, returning its key, as if it was
+    /// [inserted](ShortLeaseMap::insert). This skips the map's
+    /// [allocation strategy](crate::Builder::allocation) and any
+    /// [quarantine](crate::Builder::quarantine_for) the slot is in.
+    ///
+    /// Finding the slot on the free list takes time linear in the number of vacant slots, except
+    /// with [round robin](crate::AllocationStrategy::RoundRobin) allocation.
+    pub fn insert_at(self, t: T) -> K {
+        let now = self.map.clock.now();
+        self.map.claim(self.index);
+        self.map.occupy(self.index, t, now, None, None)
+    }
+}
@@ -0,0 +1,85 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+use crate::{Clock, LeaseKey, ShortLeaseMap};
+
+/// Chooses which value [`ShortLeaseMap::insert_evicting_with`] evicts when the map is full.
+pub trait EvictionPolicy<T, K: LeaseKey, C: Clock> {
+    /// Picks the key of the value to evict from `map`, which is full. Returns `None` only if the
+    /// map holds no values.
+    fn choose(&mut self, map: &ShortLeaseMap<T, K, C>) -> Option<K>;
+}
+
+/// Evicts the value which was inserted, or last renewed, longest ago. Constant time.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OldestFirst;
+
+impl<T, K: LeaseKey, C: Clock> EvictionPolicy<T, K, C> for OldestFirst {
+    fn choose(&mut self, map: &ShortLeaseMap<T, K, C>) -> Option<K> {
+        map.oldest().map(|(key, ..)| key)
+    }
+}
+
+/// Evicts the value whose own deadline is soonest, since it has the least time left anyway. If
+/// no value has a deadline of its own, evicts the oldest. Logarithmic time in the number of
+/// deadlines.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ShortestRemainingTtl;
+
+impl<T, K: LeaseKey, C: Clock> EvictionPolicy<T, K, C> for ShortestRemainingTtl {
+    fn choose(&mut self, map: &ShortLeaseMap<T, K, C>) -> Option<K> {
+        match map.deadlines.first() {
+            Some(&(_, index)) => Some(map.key_at(index)),
+            None => OldestFirst.choose(map),
+        }
+    }
+}
+
+/// Evicts a value picked at random, so no pattern of inserts can single out particular values.
+/// Linear time in the worst case, when most slots are vacant.
+///
+/// This isn't cryptographically secure. Each value is found by probing a random slot and moving
+/// forward to the next occupied one, so values following long runs of vacant slots are more
+/// likely to be picked.
+#[derive(Clone, Debug)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    /// Creates a policy which always makes the same choices for the same `seed`, for tests.
+    pub fn with_seed(seed: u64) -> Self {
+        // Xorshift gets stuck at zero.
+        Self { state: seed | 1 }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+}
+
+/// Seeds the policy randomly.
+impl Default for Random {
+    fn default() -> Self {
+        Self::with_seed(RandomState::new().build_hasher().finish())
+    }
+}
+
+impl<T, K: LeaseKey, C: Clock> EvictionPolicy<T, K, C> for Random {
+    fn choose(&mut self, map: &ShortLeaseMap<T, K, C>) -> Option<K> {
+        if map.is_empty() {
+            return None;
+        }
+        let slots = map.slots.len();
+        let start = (self.next_u64() % slots as u64) as usize;
+        (start..slots)
+            .chain(0..start)
+            .find(|&index| map.slots[index].occupant.is_some())
+            .map(|index| map.key_at(index))
+    }
+}
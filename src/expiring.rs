@@ -0,0 +1,216 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+use futures_core::Stream;
+use tokio::time::Sleep;
+
+use crate::{Clock, Key, ShortLeaseMap, SystemClock};
+
+/// A clock reading the time from tokio, so it follows `tokio::time::pause` and
+/// `tokio::time::advance` in tests.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioClock;
+
+impl Clock for TokioClock {
+    type Instant = Instant;
+    type Duration = Duration;
+
+    const RESOLUTION: Duration = SystemClock::RESOLUTION;
+
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+
+    fn duration_since(later: Instant, earlier: Instant) -> Duration {
+        SystemClock::duration_since(later, earlier)
+    }
+
+    fn checked_add(instant: Instant, duration: Duration) -> Option<Instant> {
+        SystemClock::checked_add(instant, duration)
+    }
+
+    fn checked_sub(instant: Instant, duration: Duration) -> Option<Instant> {
+        SystemClock::checked_sub(instant, duration)
+    }
+}
+
+/// A [`ShortLeaseMap`] where every value has a deadline, which is also a [`Stream`] yielding
+/// values as their deadlines pass.
+///
+/// A single timer is armed for the soonest deadline. The stream never ends: when the map is
+/// empty it waits for the next insert.
+///
+/// ```
+/// # #[tokio::main(flavor = "current_thread", start_paused = true)]
+/// # async fn main() {
+/// use std::time::Duration;
+///
+/// use futures_util::StreamExt;
+/// use short_lease_map::ExpiringMap;
+///
+/// let mut pending = ExpiringMap::new();
+/// let key = pending.insert("dns lookup", Duration::from_secs(2));
+/// assert_eq!(pending.next().await, Some((key, "dns lookup")));
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ExpiringMap<T> {
+    map: ShortLeaseMap<T, Key, TokioClock>,
+    /// Armed for the soonest deadline. Created on first poll, since it needs a runtime.
+    timer: Option<Pin<Box<Sleep>>>,
+    /// The task waiting on the stream, woken when an insert moves the soonest deadline earlier.
+    waker: Option<Waker>,
+}
+
+impl<T> ExpiringMap<T> {
+    /// Creates an empty ExpiringMap.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty ExpiringMap with space reserved for `size` entries.
+    pub fn with_capacity(size: usize) -> Self {
+        Self {
+            map: ShortLeaseMap::with_capacity_and_clock(size, TokioClock),
+            timer: None,
+            waker: None,
+        }
+    }
+
+    /// Adds a value which the stream will yield once `ttl` has passed. If that's further off than
+    /// [`Instant`] can represent, the stream never yields it.
+    pub fn insert(&mut self, t: T, ttl: Duration) -> Key {
+        let key = self.map.insert_with_ttl(t, ttl);
+        self.wake_if_soonest(key);
+        key
+    }
+
+    /// Adds a value which the stream will yield at `deadline`.
+    pub fn insert_with_deadline(&mut self, t: T, deadline: Instant) -> Key {
+        let key = self.map.insert_with_deadline(t, deadline);
+        self.wake_if_soonest(key);
+        key
+    }
+
+    /// Gets the value for this key. Returns `None` if the key is stale.
+    pub fn get(&self, key: Key) -> Option<&T> {
+        self.map.get(key)
+    }
+
+    /// Gets the value for this key mutably. Returns `None` if the key is stale.
+    pub fn get_mut(&mut self, key: Key) -> Option<&mut T> {
+        self.map.get_mut(key)
+    }
+
+    /// Removes the value with this key, so the stream won't yield it.
+    pub fn remove(&mut self, key: Key) -> Option<T> {
+        self.map.remove(key)
+    }
+
+    /// Gives the value for this key a fresh deadline, as far from now as the TTL it was inserted
+    /// with. Returns `false` if the key is stale.
+    pub fn renew(&mut self, key: Key) -> bool {
+        self.map.renew(key)
+    }
+
+    /// Pushes back the deadline for this key by `by`, returning the new deadline. Returns `None`
+    /// if the key is stale, or the new deadline is further off than [`Instant`] can represent.
+    pub fn extend(&mut self, key: Key, by: Duration) -> Option<Instant> {
+        self.map.extend(key, by)
+    }
+
+    /// The deadline for this key.
+    pub fn deadline(&self, key: Key) -> Option<Instant> {
+        self.map.deadline(key)
+    }
+
+    /// The map behind the stream, for read only queries such as
+    /// [`ShortLeaseMap::iter_leases`].
+    pub fn as_map(&self) -> &ShortLeaseMap<T, Key, TokioClock> {
+        &self.map
+    }
+
+    fn wake_if_soonest(&mut self, key: Key) {
+        if self.map.next_deadline() == self.map.deadline(key) {
+            if let Some(waker) = self.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl<T> Default for ExpiringMap<T> {
+    fn default() -> Self {
+        Self::with_capacity(0)
+    }
+}
+
+// Values are never pinned, only moved in and out of the map.
+impl<T> Unpin for ExpiringMap<T> {}
+
+impl<T> Stream for ExpiringMap<T> {
+    type Item = (Key, T);
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some((key, value, _)) = this.map.pop_overdue(this.map.clock().now()) {
+                return Poll::Ready(Some((key, value)));
+            }
+            this.waker = Some(cx.waker().clone());
+            let Some(deadline) = this.map.next_deadline() else {
+                return Poll::Pending;
+            };
+            let deadline = tokio::time::Instant::from_std(deadline);
+            let timer = this
+                .timer
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(deadline)));
+            if timer.deadline() != deadline {
+                timer.as_mut().reset(deadline);
+            }
+            if timer.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::{poll, StreamExt};
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn yields_in_deadline_order() {
+        let mut map = ExpiringMap::new();
+        let bulk = map.insert("bulk rpc", Duration::from_secs(60));
+        let dns = map.insert("dns lookup", Duration::from_secs(2));
+        let cancelled = map.insert("cancelled", Duration::from_secs(1));
+        assert_eq!(map.remove(cancelled), Some("cancelled"));
+        let start = tokio::time::Instant::now();
+        assert_eq!(map.next().await, Some((dns, "dns lookup")));
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+        assert_eq!(map.next().await, Some((bulk, "bulk rpc")));
+        assert_eq!(start.elapsed(), Duration::from_secs(60));
+        assert!(poll!(map.next()).is_pending());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn insert_moves_timer_earlier() {
+        let mut map = ExpiringMap::new();
+        let start = tokio::time::Instant::now();
+        let slow = map.insert("slow", Duration::from_secs(10));
+        assert!(poll!(map.next()).is_pending());
+        let fast = map.insert("fast", Duration::from_secs(1));
+        assert_eq!(map.next().await, Some((fast, "fast")));
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+        assert!(map.renew(slow));
+        assert_eq!(map.next().await, Some((slow, "slow")));
+        assert_eq!(start.elapsed(), Duration::from_secs(11));
+    }
+}
@@ -0,0 +1,96 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, BinaryHeap, VecDeque},
+};
+
+/// How [`ShortLeaseMap::insert`](crate::ShortLeaseMap::insert) picks which vacant slot to reuse.
+/// New slots are only added once no vacant slot is left.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum AllocationStrategy {
+    /// Reuse the most recently vacated slot, whose memory is most likely still in cache. Constant
+    /// time.
+    #[default]
+    Lifo,
+    /// Reuse the slot vacated longest ago, maximising the time before a key's index is handed out
+    /// again. Constant time.
+    Fifo,
+    /// Reuse the lowest vacant slot, keeping values packed at the front of the map. Logarithmic
+    /// time.
+    LowestFirst,
+    /// Reuse the first vacant slot after the one handed out last, wrapping around at the end, so
+    /// indices are handed out in rotation. Logarithmic time.
+    RoundRobin,
+}
+
+/// Vacant slots ready to be reused, in the order an [`AllocationStrategy`] wants them.
+#[derive(Clone, Debug)]
+pub(crate) enum FreeList {
+    Lifo(Vec<usize>),
+    Fifo(VecDeque<usize>),
+    LowestFirst(BinaryHeap<Reverse<usize>>),
+    RoundRobin {
+        vacant: BTreeSet<usize>,
+        /// Where to start looking for the next vacant slot.
+        cursor: usize,
+    },
+}
+
+impl FreeList {
+    pub(crate) fn new(strategy: AllocationStrategy) -> Self {
+        match strategy {
+            AllocationStrategy::Lifo => Self::Lifo(Vec::new()),
+            AllocationStrategy::Fifo => Self::Fifo(VecDeque::new()),
+            AllocationStrategy::LowestFirst => Self::LowestFirst(BinaryHeap::new()),
+            AllocationStrategy::RoundRobin => Self::RoundRobin {
+                vacant: BTreeSet::new(),
+                cursor: 0,
+            },
+        }
+    }
+
+    /// Makes a vacant slot available for reuse.
+    pub(crate) fn push(&mut self, index: usize) {
+        match self {
+            Self::Lifo(free) => free.push(index),
+            Self::Fifo(free) => free.push_back(index),
+            Self::LowestFirst(free) => free.push(Reverse(index)),
+            Self::RoundRobin { vacant, .. } => {
+                vacant.insert(index);
+            }
+        }
+    }
+
+    /// Takes the next slot to reuse.
+    pub(crate) fn pop(&mut self) -> Option<usize> {
+        match self {
+            Self::Lifo(free) => free.pop(),
+            Self::Fifo(free) => free.pop_front(),
+            Self::LowestFirst(free) => free.pop().map(|Reverse(index)| index),
+            Self::RoundRobin { vacant, cursor } => {
+                let index = *vacant.range(*cursor..).next().or(vacant.first())?;
+                vacant.remove(&index);
+                *cursor = index + 1;
+                Some(index)
+            }
+        }
+    }
+
+    /// Takes a particular slot off the list, if it's on it. Linear time, except for round robin.
+    pub(crate) fn remove(&mut self, index: usize) {
+        match self {
+            Self::Lifo(free) => free.retain(|&i| i != index),
+            Self::Fifo(free) => free.retain(|&i| i != index),
+            Self::LowestFirst(free) => free.retain(|&Reverse(i)| i != index),
+            Self::RoundRobin { vacant, .. } => {
+                vacant.remove(&index);
+            }
+        }
+    }
+
+    /// Records that a brand new slot was handed out because nothing was free.
+    pub(crate) fn grew(&mut self, index: usize) {
+        if let Self::RoundRobin { cursor, .. } = self {
+            *cursor = index + 1;
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::{
+    expect_room, CapacityError, Clock, Key, ReapedShortLeaseMap, ShortLeaseMap, SystemClock,
+};
+
+/// A value in a shared [`ShortLeaseMap`] which is removed when the guard is dropped, so error
+/// paths can't forget to check out.
+///
+/// The lease still expires as usual. If the value has already been evicted, the accessors return
+/// `None` and dropping the guard does nothing.
+#[derive(Debug)]
+pub struct LeaseGuard<T, C: Clock = SystemClock> {
+    map: Arc<Mutex<ShortLeaseMap<T, Key, C>>>,
+    key: Key,
+    /// Whether dropping the guard removes the value.
+    armed: bool,
+}
+
+impl<T, C: Clock> LeaseGuard<T, C> {
+    /// Adds a value to the shared map, returning a guard which removes it again when dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map is [full](ShortLeaseMap::try_insert). Use
+    /// [`try_insert`](Self::try_insert) to handle that instead.
+    pub fn insert(map: &Arc<Mutex<ShortLeaseMap<T, Key, C>>>, t: T) -> Self {
+        expect_room(Self::try_insert(map, t))
+    }
+
+    /// Adds a value to the shared map like [`insert`](Self::insert), but if the map is full, hands
+    /// the value back instead of panicking.
+    pub fn try_insert(
+        map: &Arc<Mutex<ShortLeaseMap<T, Key, C>>>,
+        t: T,
+    ) -> Result<Self, CapacityError<T>> {
+        let key = map.lock().unwrap().try_insert(t)?;
+        Ok(Self {
+            map: Arc::clone(map),
+            key,
+            armed: true,
+        })
+    }
+
+    /// The key of the guarded value.
+    pub fn key(&self) -> Key {
+        self.key
+    }
+
+    /// Calls `f` with a reference to the guarded value while the map is locked. Returns `None` if
+    /// the value has been evicted.
+    pub fn with<R>(&self, f: impl FnOnce(&T) -> R) -> Option<R> {
+        self.lock().get(self.key).map(f)
+    }
+
+    /// Calls `f` with a mutable reference to the guarded value while the map is locked. Returns
+    /// `None` if the value has been evicted.
+    pub fn with_mut<R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        self.lock().get_mut(self.key).map(f)
+    }
+
+    /// Clones the guarded value. Returns `None` if the value has been evicted.
+    pub fn get(&self) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.with(T::clone)
+    }
+
+    /// Removes the value from the map and returns it. Returns `None` if the value has been
+    /// evicted.
+    pub fn into_inner(mut self) -> Option<T> {
+        self.armed = false;
+        self.lock().remove(self.key)
+    }
+
+    /// Leaves the value in the map and returns its key, so it's up to the caller to remove it
+    /// again.
+    pub fn into_key(mut self) -> Key {
+        self.armed = false;
+        self.key
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ShortLeaseMap<T, Key, C>> {
+        self.map.lock().unwrap()
+    }
+}
+
+impl<T, C: Clock> Drop for LeaseGuard<T, C> {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        // Don't pile a second panic on top of whichever one poisoned the map.
+        if let Ok(mut map) = self.map.lock() {
+            map.remove(self.key);
+        }
+    }
+}
+
+impl<T, C: Clock> ReapedShortLeaseMap<T, C> {
+    /// Adds a value to the map, returning a guard which removes it again when dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map is full. Use [`try_insert_guarded`](Self::try_insert_guarded) to handle
+    /// that instead.
+    pub fn insert_guarded(&self, t: T) -> LeaseGuard<T, C> {
+        LeaseGuard::insert(self.shared(), t)
+    }
+
+    /// Adds a value to the map like [`insert_guarded`](Self::insert_guarded), but if the map is
+    /// full, hands the value back instead of panicking.
+    pub fn try_insert_guarded(&self, t: T) -> Result<LeaseGuard<T, C>, CapacityError<T>> {
+        LeaseGuard::try_insert(self.shared(), t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{Builder, TickClock};
+
+    #[test]
+    fn removes_on_drop() {
+        let map = Arc::new(Mutex::new(ShortLeaseMap::new()));
+        let guard = LeaseGuard::insert(&map, 1);
+        let key = guard.key();
+        assert_eq!(guard.with_mut(|v| std::mem::replace(v, 2)), Some(1));
+        assert_eq!(guard.get(), Some(2));
+        drop(guard);
+        assert_eq!(map.lock().unwrap().get(key), None);
+
+        let kept = LeaseGuard::insert(&map, 3).into_key();
+        assert_eq!(map.lock().unwrap().get(kept), Some(&3));
+        assert_eq!(LeaseGuard::insert(&map, 4).into_inner(), Some(4));
+    }
+
+    #[test]
+    fn full() {
+        let map = Arc::new(Mutex::new(Builder::new().max_slots(1).build()));
+        let guard = LeaseGuard::insert(&map, 1);
+        assert_eq!(LeaseGuard::try_insert(&map, 2).unwrap_err().into_inner(), 2);
+        assert!(std::panic::catch_unwind(|| LeaseGuard::insert(&map, 3)).is_err());
+        assert!(!map.is_poisoned());
+        drop(guard);
+        assert!(LeaseGuard::try_insert(&map, 4).is_ok());
+    }
+
+    #[test]
+    fn tolerates_eviction() {
+        let map = Arc::new(Mutex::new(Builder::with_clock(TickClock::new(0)).build()));
+        let guard = LeaseGuard::insert(&map, "evicted");
+        map.lock().unwrap().advance_ticks(2);
+        map.lock().unwrap().dump_older_than_ticks(1);
+        let tenant = map.lock().unwrap().insert("new tenant");
+        assert_eq!(tenant.index(), guard.key().index());
+        assert_eq!(guard.get(), None);
+        drop(guard);
+        assert_eq!(map.lock().unwrap().get(tenant), Some(&"new tenant"));
+    }
+
+    #[test]
+    fn reaped_map() {
+        let map = ReapedShortLeaseMap::spawn(
+            ShortLeaseMap::new(),
+            None,
+            Duration::from_secs(3600),
+            |_, _: ()| {},
+        );
+        let key = map.insert_guarded(()).key();
+        assert_eq!(map.get(key), None);
+    }
+}
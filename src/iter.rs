@@ -0,0 +1,360 @@
+use std::{iter::Enumerate, marker::PhantomData, slice, vec};
+
+use crate::{Clock, Departure, Key, LeaseKey, Occupant, ShortLeaseMap, Slot, SystemClock};
+
+/// Walks the occupied slots of a map in slot order, from either end, keeping count of how many
+/// are left. Every iterator over slots is built on this, so they agree on keys and occupancy.
+#[derive(Clone, Debug)]
+struct Occupied<I> {
+    slots: Enumerate<I>,
+    /// How many occupied slots are left.
+    len: usize,
+}
+
+/// A slot as held by one of the iterators, whether borrowed, mutably borrowed or owned.
+trait SlotHandle {
+    type Occupant;
+
+    /// The slot's generation, and its occupant if there is one.
+    fn into_parts(self) -> (u32, Option<Self::Occupant>);
+}
+
+impl<'a, T, C: Clock> SlotHandle for &'a Slot<T, C> {
+    type Occupant = &'a Occupant<T, C>;
+
+    fn into_parts(self) -> (u32, Option<Self::Occupant>) {
+        (self.generation, self.occupant.as_ref())
+    }
+}
+
+impl<'a, T, C: Clock> SlotHandle for &'a mut Slot<T, C> {
+    type Occupant = &'a mut Occupant<T, C>;
+
+    fn into_parts(self) -> (u32, Option<Self::Occupant>) {
+        (self.generation, self.occupant.as_mut())
+    }
+}
+
+impl<T, C: Clock> SlotHandle for Slot<T, C> {
+    type Occupant = Occupant<T, C>;
+
+    fn into_parts(self) -> (u32, Option<Self::Occupant>) {
+        (self.generation, self.occupant)
+    }
+}
+
+impl<I> Occupied<I>
+where
+    I: DoubleEndedIterator + ExactSizeIterator,
+    I::Item: SlotHandle,
+{
+    fn new(slots: I, len: usize) -> Self {
+        Self {
+            slots: slots.enumerate(),
+            len,
+        }
+    }
+
+    fn next<K: LeaseKey>(&mut self) -> Option<(K, <I::Item as SlotHandle>::Occupant)> {
+        let item = self.slots.find_map(Self::occupant)?;
+        self.len -= 1;
+        Some(item)
+    }
+
+    fn next_back<K: LeaseKey>(&mut self) -> Option<(K, <I::Item as SlotHandle>::Occupant)> {
+        let item = self.slots.by_ref().rev().find_map(Self::occupant)?;
+        self.len -= 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+
+    fn occupant<K: LeaseKey>(
+        (index, slot): (usize, I::Item),
+    ) -> Option<(K, <I::Item as SlotHandle>::Occupant)> {
+        let (generation, occupant) = slot.into_parts();
+        occupant.map(|o| (K::from_parts(index, generation), o))
+    }
+}
+
+/// An iterator over references to the values of a [`ShortLeaseMap`] and their keys, in slot
+/// order. Made by [`ShortLeaseMap::iter`].
+#[derive(Debug)]
+pub struct Iter<'a, T, K = Key, C: Clock = SystemClock> {
+    slots: Occupied<slice::Iter<'a, Slot<T, C>>>,
+    _key: PhantomData<fn() -> K>,
+}
+
+impl<'a, T, K, C: Clock> Iter<'a, T, K, C> {
+    pub(crate) fn new(slots: &'a [Slot<T, C>], len: usize) -> Self {
+        Self {
+            slots: Occupied::new(slots.iter(), len),
+            _key: PhantomData,
+        }
+    }
+}
+
+impl<T, K, C: Clock> Clone for Iter<'_, T, K, C> {
+    fn clone(&self) -> Self {
+        Self {
+            slots: self.slots.clone(),
+            _key: PhantomData,
+        }
+    }
+}
+
+impl<'a, T, K: LeaseKey, C: Clock> Iterator for Iter<'a, T, K, C> {
+    type Item = (&'a T, K);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.slots.next().map(|(key, o)| (&o.value, key))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.slots.size_hint()
+    }
+}
+
+impl<T, K: LeaseKey, C: Clock> DoubleEndedIterator for Iter<'_, T, K, C> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.slots.next_back().map(|(key, o)| (&o.value, key))
+    }
+}
+
+impl<T, K: LeaseKey, C: Clock> ExactSizeIterator for Iter<'_, T, K, C> {}
+
+/// An iterator over mutable references to the values of a [`ShortLeaseMap`] and their keys, in
+/// slot order. Made by [`ShortLeaseMap::iter_mut`].
+#[derive(Debug)]
+pub struct IterMut<'a, T, K = Key, C: Clock = SystemClock> {
+    slots: Occupied<slice::IterMut<'a, Slot<T, C>>>,
+    _key: PhantomData<fn() -> K>,
+}
+
+impl<'a, T, K, C: Clock> IterMut<'a, T, K, C> {
+    pub(crate) fn new(slots: &'a mut [Slot<T, C>], len: usize) -> Self {
+        Self {
+            slots: Occupied::new(slots.iter_mut(), len),
+            _key: PhantomData,
+        }
+    }
+}
+
+impl<'a, T, K: LeaseKey, C: Clock> Iterator for IterMut<'a, T, K, C> {
+    type Item = (&'a mut T, K);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.slots.next().map(|(key, o)| (&mut o.value, key))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.slots.size_hint()
+    }
+}
+
+impl<T, K: LeaseKey, C: Clock> DoubleEndedIterator for IterMut<'_, T, K, C> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.slots.next_back().map(|(key, o)| (&mut o.value, key))
+    }
+}
+
+impl<T, K: LeaseKey, C: Clock> ExactSizeIterator for IterMut<'_, T, K, C> {}
+
+/// An iterator over the keys of a [`ShortLeaseMap`], references to their values, and when each
+/// was inserted, in slot order. Made by [`ShortLeaseMap::iter_leases`].
+#[derive(Debug)]
+pub struct Leases<'a, T, K = Key, C: Clock = SystemClock> {
+    slots: Occupied<slice::Iter<'a, Slot<T, C>>>,
+    _key: PhantomData<fn() -> K>,
+}
+
+impl<'a, T, K, C: Clock> Leases<'a, T, K, C> {
+    pub(crate) fn new(slots: &'a [Slot<T, C>], len: usize) -> Self {
+        Self {
+            slots: Occupied::new(slots.iter(), len),
+            _key: PhantomData,
+        }
+    }
+}
+
+impl<T, K, C: Clock> Clone for Leases<'_, T, K, C> {
+    fn clone(&self) -> Self {
+        Self {
+            slots: self.slots.clone(),
+            _key: PhantomData,
+        }
+    }
+}
+
+impl<'a, T, K: LeaseKey, C: Clock> Iterator for Leases<'a, T, K, C> {
+    type Item = (K, &'a T, C::Instant);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.slots
+            .next()
+            .map(|(key, o)| (key, &o.value, o.inserted_at))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.slots.size_hint()
+    }
+}
+
+impl<T, K: LeaseKey, C: Clock> DoubleEndedIterator for Leases<'_, T, K, C> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.slots
+            .next_back()
+            .map(|(key, o)| (key, &o.value, o.inserted_at))
+    }
+}
+
+impl<T, K: LeaseKey, C: Clock> ExactSizeIterator for Leases<'_, T, K, C> {}
+
+/// An iterator over the keys of a [`ShortLeaseMap`], mutable references to their values, and when
+/// each was inserted, in slot order. Made by [`ShortLeaseMap::iter_leases_mut`].
+#[derive(Debug)]
+pub struct LeasesMut<'a, T, K = Key, C: Clock = SystemClock> {
+    slots: Occupied<slice::IterMut<'a, Slot<T, C>>>,
+    _key: PhantomData<fn() -> K>,
+}
+
+impl<'a, T, K, C: Clock> LeasesMut<'a, T, K, C> {
+    pub(crate) fn new(slots: &'a mut [Slot<T, C>], len: usize) -> Self {
+        Self {
+            slots: Occupied::new(slots.iter_mut(), len),
+            _key: PhantomData,
+        }
+    }
+}
+
+impl<'a, T, K: LeaseKey, C: Clock> Iterator for LeasesMut<'a, T, K, C> {
+    type Item = (K, &'a mut T, C::Instant);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.slots
+            .next()
+            .map(|(key, o)| (key, &mut o.value, o.inserted_at))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.slots.size_hint()
+    }
+}
+
+impl<T, K: LeaseKey, C: Clock> DoubleEndedIterator for LeasesMut<'_, T, K, C> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.slots
+            .next_back()
+            .map(|(key, o)| (key, &mut o.value, o.inserted_at))
+    }
+}
+
+impl<T, K: LeaseKey, C: Clock> ExactSizeIterator for LeasesMut<'_, T, K, C> {}
+
+/// An iterator taking the values out of a [`ShortLeaseMap`] along with their keys, in slot order.
+/// Made by the map's [`IntoIterator`] implementation.
+#[derive(Debug)]
+pub struct IntoIter<T, K = Key, C: Clock = SystemClock> {
+    slots: Occupied<vec::IntoIter<Slot<T, C>>>,
+    _key: PhantomData<fn() -> K>,
+}
+
+impl<T, K: LeaseKey, C: Clock> Iterator for IntoIter<T, K, C> {
+    type Item = (T, K);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.slots.next().map(|(key, o)| (o.value, key))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.slots.size_hint()
+    }
+}
+
+impl<T, K: LeaseKey, C: Clock> DoubleEndedIterator for IntoIter<T, K, C> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.slots.next_back().map(|(key, o)| (o.value, key))
+    }
+}
+
+impl<T, K: LeaseKey, C: Clock> ExactSizeIterator for IntoIter<T, K, C> {}
+
+impl<T, K: LeaseKey, C: Clock> IntoIterator for ShortLeaseMap<T, K, C> {
+    type Item = (T, K);
+    type IntoIter = IntoIter<T, K, C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            slots: Occupied::new(self.slots.into_iter(), self.len),
+            _key: PhantomData,
+        }
+    }
+}
+
+impl<'a, T, K: LeaseKey, C: Clock> IntoIterator for &'a ShortLeaseMap<T, K, C> {
+    type Item = (&'a T, K);
+    type IntoIter = Iter<'a, T, K, C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T, K: LeaseKey, C: Clock> IntoIterator for &'a mut ShortLeaseMap<T, K, C> {
+    type Item = (&'a mut T, K);
+    type IntoIter = IterMut<'a, T, K, C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// An iterator removing every value from a [`ShortLeaseMap`], yielding each value and its key,
+/// oldest first. Made by [`ShortLeaseMap::drain`].
+///
+/// Like [`Vec::drain`], dropping it part way through still removes the rest.
+#[derive(Debug)]
+pub struct Drain<'a, T, K: LeaseKey = Key, C: Clock = SystemClock> {
+    map: &'a mut ShortLeaseMap<T, K, C>,
+}
+
+impl<'a, T, K: LeaseKey, C: Clock> Drain<'a, T, K, C> {
+    pub(crate) fn new(map: &'a mut ShortLeaseMap<T, K, C>) -> Self {
+        Self { map }
+    }
+
+    fn take(&mut self, index: usize) -> (T, K) {
+        let key = self.map.key_at(index);
+        (self.map.vacate(index, Departure::Removed).value, key)
+    }
+}
+
+impl<T, K: LeaseKey, C: Clock> Iterator for Drain<'_, T, K, C> {
+    type Item = (T, K);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let head = self.map.head?;
+        Some(self.take(head))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.map.len, Some(self.map.len))
+    }
+}
+
+impl<T, K: LeaseKey, C: Clock> DoubleEndedIterator for Drain<'_, T, K, C> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let tail = self.map.tail?;
+        Some(self.take(tail))
+    }
+}
+
+impl<T, K: LeaseKey, C: Clock> ExactSizeIterator for Drain<'_, T, K, C> {}
+
+impl<T, K: LeaseKey, C: Clock> Drop for Drain<'_, T, K, C> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
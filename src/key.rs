@@ -0,0 +1,149 @@
+use std::fmt::Debug;
+
+/// The key handed out by [`ShortLeaseMap::insert`](crate::ShortLeaseMap::insert).
+///
+/// Rooms get reused, so a key is the room number plus the generation of the guest staying in
+/// it. Each time a room is vacated its generation advances, so a key held by a previous guest
+/// will no longer match, and lookups with it return `None` rather than the new guest's value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Key {
+    index: usize,
+    generation: u32,
+}
+
+impl Key {
+    /// Rebuilds a key from its parts, such as after receiving them over the wire.
+    pub fn from_parts(index: usize, generation: u32) -> Self {
+        Self { index, generation }
+    }
+
+    /// The slot this key refers to. Slots are reused, so this alone is not unique over time.
+    pub fn index(self) -> usize {
+        self.index
+    }
+
+    /// The generation of the slot at the time this key was handed out.
+    pub fn generation(self) -> u32 {
+        self.generation
+    }
+}
+
+/// A type which can be used as the key of a [`ShortLeaseMap`](crate::ShortLeaseMap).
+///
+/// Giving each map its own key type stops keys from one map being used with another. The easiest
+/// way to get one is [`new_key_type!`](crate::new_key_type).
+///
+/// The plain integers `u8`, `u16`, `u32` and `usize` are keys too, for when keys have to fit a
+/// narrow field on the wire. They are just the slot index, so they carry no generation, and
+/// a map using them holds at most as many values as the integer can count. See
+/// [`ShortLeaseMap::try_insert`](crate::ShortLeaseMap::try_insert).
+pub trait LeaseKey: Copy + Eq + Debug {
+    /// The highest slot index this key type can represent.
+    const MAX_INDEX: usize = usize::MAX;
+
+    /// Builds a key from a slot index and the generation of that slot. The index is never more
+    /// than [`MAX_INDEX`](Self::MAX_INDEX).
+    fn from_parts(index: usize, generation: u32) -> Self;
+
+    /// The slot this key refers to.
+    fn index(self) -> usize;
+
+    /// The generation of the slot at the time this key was handed out, or `None` if this key type
+    /// doesn't record one, in which case the key matches whoever holds the slot.
+    fn generation(self) -> Option<u32>;
+}
+
+impl LeaseKey for Key {
+    fn from_parts(index: usize, generation: u32) -> Self {
+        Key::from_parts(index, generation)
+    }
+
+    fn index(self) -> usize {
+        Key::index(self)
+    }
+
+    fn generation(self) -> Option<u32> {
+        Some(Key::generation(self))
+    }
+}
+
+macro_rules! impl_integer_key {
+    ($($int:ty),*) => {
+        $(
+            impl LeaseKey for $int {
+                const MAX_INDEX: usize = <$int>::MAX as usize;
+
+                fn from_parts(index: usize, _generation: u32) -> Self {
+                    index as $int
+                }
+
+                fn index(self) -> usize {
+                    self as usize
+                }
+
+                fn generation(self) -> Option<u32> {
+                    None
+                }
+            }
+        )*
+    };
+}
+
+impl_integer_key!(u8, u16, u32, usize);
+
+/// Declares new key types, wrapping [`Key`], for use with maps whose keys shouldn't mix.
+///
+/// ```
+/// use short_lease_map::{new_key_type, Builder, ShortLeaseMap};
+///
+/// new_key_type! {
+///     /// Identifies a session.
+///     pub struct SessionKey;
+///     /// Identifies a request awaiting its response.
+///     pub struct RequestKey;
+/// }
+///
+/// let mut sessions: ShortLeaseMap<&str, SessionKey> = Builder::new().keys().build();
+/// let mut requests: ShortLeaseMap<&str, RequestKey> = Builder::new().keys().build();
+/// let session = sessions.insert("alice");
+/// let request = requests.insert("GET /");
+/// assert_eq!(requests.get(request), Some(&"GET /"));
+/// // requests.get(session) would not compile.
+/// ```
+#[macro_export]
+macro_rules! new_key_type {
+    ($(#[$outer:meta])* $vis:vis struct $name:ident; $($rest:tt)*) => {
+        $(#[$outer])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        $vis struct $name($crate::Key);
+
+        impl $crate::LeaseKey for $name {
+            fn from_parts(index: usize, generation: u32) -> Self {
+                Self($crate::Key::from_parts(index, generation))
+            }
+
+            fn index(self) -> usize {
+                self.0.index()
+            }
+
+            fn generation(self) -> ::std::option::Option<u32> {
+                ::std::option::Option::Some(self.0.generation())
+            }
+        }
+
+        impl ::std::convert::From<$crate::Key> for $name {
+            fn from(key: $crate::Key) -> Self {
+                Self(key)
+            }
+        }
+
+        impl ::std::convert::From<$name> for $crate::Key {
+            fn from(key: $name) -> Self {
+                key.0
+            }
+        }
+
+        $crate::new_key_type!($($rest)*);
+    };
+    () => {};
+}
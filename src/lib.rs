@@ -1,11 +1,142 @@
-use std::time::{Duration, Instant};
+use std::{
+    collections::{BTreeSet, VecDeque},
+    error::Error,
+    fmt,
+    marker::PhantomData,
+    ops::{Index, IndexMut},
+};
+
+use free_list::FreeList;
+
+mod builder;
+mod clock;
+mod concurrent;
+mod correlator;
+mod entry;
+mod eviction;
+#[cfg(feature = "tokio")]
+mod expiring;
+mod free_list;
+mod guard;
+mod iter;
+mod key;
+mod reaper;
+mod reserve;
+#[cfg(feature = "serde")]
+mod snapshot;
+
+pub use builder::Builder;
+pub use clock::{Clock, ManualClock, SystemClock, TickClock};
+pub use concurrent::{ConcurrentShortLeaseMap, ShardedKey, ShardedVacantLease};
+pub use correlator::{CompleteError, Correlator, TimedOut};
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+pub use eviction::{EvictionPolicy, OldestFirst, Random, ShortestRemainingTtl};
+#[cfg(feature = "tokio")]
+pub use expiring::{ExpiringMap, TokioClock};
+pub use free_list::AllocationStrategy;
+pub use guard::LeaseGuard;
+pub use iter::{Drain, IntoIter, Iter, IterMut, Leases, LeasesMut};
+pub use key::{Key, LeaseKey};
+pub use reaper::ReapedShortLeaseMap;
+pub use reserve::{SharedVacantLease, VacantLease};
 
 /// A HashMap like collection, but optimized for really short term internship.
 ///
 /// It's easiest to think of this like a hotel. When you check in, a room number
 /// is assigned to you. When you leave, that room can now be assigned to someone else.
+///
+/// Time is read from a [`Clock`], which defaults to the system clock. Keys are [`Key`]s, unless
+/// another [`LeaseKey`] is chosen with [`Builder::keys`].
+#[derive(Clone, Debug)]
+pub struct ShortLeaseMap<T, K = Key, C: Clock = SystemClock> {
+    slots: Vec<Slot<T, C>>,
+    /// How many slots are occupied.
+    len: usize,
+    /// How many slots there can be, including vacant ones.
+    max_slots: usize,
+    /// Vacant slots ready to be reused.
+    free: FreeList,
+    /// Recently vacated slots waiting to go on the free list, with when they were vacated and
+    /// the allocation count at the time, oldest first.
+    quarantine: VecDeque<(usize, C::Instant, u64)>,
+    quarantine_time: Option<C::Duration>,
+    quarantine_allocations: u64,
+    /// How many values have been inserted over the map's lifetime.
+    allocations: u64,
+    /// The longest staying guest. Occupied slots form a doubly linked list in check in order.
+    head: Option<usize>,
+    /// The most recent guest.
+    tail: Option<usize>,
+    /// Guests that were given their own deadline, soonest first.
+    deadlines: BTreeSet<(C::Instant, usize)>,
+    clock: C,
+    /// Whether `get_mut` renews leases.
+    sliding: bool,
+    /// Whether vacated slots remember why and when they were vacated.
+    tombstones: bool,
+    _key: PhantomData<fn() -> K>,
+}
+
+#[derive(Clone, Debug)]
+struct Slot<T, C: Clock> {
+    generation: u32,
+    occupant: Option<Occupant<T, C>>,
+    /// Whether the slot is vacant but held by [`ShortLeaseMap::reserve`] until its value arrives.
+    reserved: bool,
+    /// Why the previous occupant left, if the map keeps tombstones and the slot is vacant.
+    tombstone: Option<Tombstone<C::Instant>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Tombstone<I> {
+    departure: Departure,
+    at: I,
+}
+
+/// Why an occupant left its slot.
+#[derive(Clone, Copy, Debug)]
+enum Departure {
+    Removed,
+    Expired,
+}
+
+/// What [`ShortLeaseMap::lookup`] found for a key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lookup<'a, T, I> {
+    /// The key is current, and this is its value.
+    Occupied(&'a T),
+    /// The key was handed out by [`ShortLeaseMap::reserve`], and its value hasn't arrived yet.
+    Reserved,
+    /// The value was removed at this time.
+    Removed { at: I },
+    /// The value expired at this time, and was evicted by a sweep.
+    Expired { at: I },
+    /// The key was handed out once, but its value is gone and nothing records why. Either the map
+    /// doesn't keep [tombstones](Builder::tombstones), or the slot has had other occupants since.
+    Stale,
+    /// The key was never handed out by this map.
+    NeverIssued,
+}
+
 #[derive(Clone, Debug)]
-pub struct ShortLeaseMap<T>(Vec<Option<(T, Instant)>>);
+struct Occupant<T, C: Clock> {
+    value: T,
+    inserted_at: C::Instant,
+    deadline: Option<C::Instant>,
+    /// The TTL the value was inserted with, so renewing can move its deadline too.
+    ttl: Option<C::Duration>,
+    prev: Option<usize>,
+    next: Option<usize>,
+}
+
+impl<T, C: Clock> Slot<T, C> {
+    /// The occupant, if its generation matches. Keys without a generation match any occupant.
+    fn get(&self, generation: Option<u32>) -> Option<&Occupant<T, C>> {
+        self.occupant
+            .as_ref()
+            .filter(|_| generation.is_none_or(|g| g == self.generation))
+    }
+}
 
 impl<T> ShortLeaseMap<T> {
     /// Creates a new ShortLeaseMap with zero capacity. Capacity will grow as items are added.
@@ -15,95 +146,1434 @@ impl<T> ShortLeaseMap<T> {
 
     /// Creates a new ShortLeaseMap with space reserved for `size` entries.
     pub fn with_capacity(size: usize) -> Self {
-        Self(Vec::with_capacity(size))
-    }
-
-    /// Adds a value to the map. The value returned can later be used to retrieve it. The returned
-    /// key is not guaranteed to be unique once the value has been removed from this map.
-    pub fn insert(&mut self, t: T) -> usize {
-        let idx = self.0.iter_mut().enumerate().find(|(_, v)| v.is_none());
-        match idx {
-            None => {
-                let idx = self.0.len();
-                self.0.push(Some((t, Instant::now())));
-                idx
-            }
-            Some((i, v)) => {
-                *v = Some((t, Instant::now()));
-                i
+        Self::with_capacity_and_clock(size, SystemClock)
+    }
+}
+
+impl<T, C: Clock> ShortLeaseMap<T, Key, C> {
+    /// Creates a new ShortLeaseMap which reads the time from `clock`.
+    pub fn with_clock(clock: C) -> Self {
+        Self::with_capacity_and_clock(0, clock)
+    }
+
+    /// Creates a new ShortLeaseMap with space reserved for `size` entries, which reads the time
+    /// from `clock`.
+    pub fn with_capacity_and_clock(size: usize, clock: C) -> Self {
+        Builder::with_clock(clock).capacity(size).build()
+    }
+}
+
+impl<T> ShortLeaseMap<T, Key, TickClock> {
+    /// Creates a new ShortLeaseMap which measures lease ages in ticks, starting from tick zero.
+    pub fn with_ticks() -> Self {
+        Self::default()
+    }
+}
+
+impl<T, K: LeaseKey> ShortLeaseMap<T, K, TickClock> {
+    /// The current tick.
+    pub fn current_tick(&self) -> u64 {
+        self.clock.tick()
+    }
+
+    /// Moves the clock forward by `ticks`.
+    pub fn advance_ticks(&mut self, ticks: u64) {
+        self.clock.advance(ticks);
+    }
+
+    /// Moves the clock to `tick`, such as the current frame number.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tick` is earlier than the current tick.
+    pub fn set_tick(&mut self, tick: u64) {
+        self.clock.set(tick);
+    }
+
+    /// Evict guests which were inserted more than `ticks` ticks ago. Returns a count of how many
+    /// items were removed.
+    pub fn dump_older_than_ticks(&mut self, ticks: u64) -> usize {
+        self.dump_old_values(ticks)
+    }
+}
+
+impl<T, K: LeaseKey, C: Clock> ShortLeaseMap<T, K, C> {
+    fn from_builder(builder: Builder<K, C>) -> Self {
+        Self {
+            slots: Vec::with_capacity(builder.capacity),
+            len: 0,
+            max_slots: builder
+                .max_slots
+                .unwrap_or(usize::MAX)
+                .min(K::MAX_INDEX.saturating_add(1)),
+            free: FreeList::new(builder.allocation),
+            quarantine: VecDeque::new(),
+            quarantine_time: builder.quarantine_time,
+            quarantine_allocations: builder.quarantine_allocations,
+            allocations: 0,
+            head: None,
+            tail: None,
+            deadlines: BTreeSet::new(),
+            clock: builder.clock,
+            sliding: builder.sliding,
+            tombstones: builder.tombstones,
+            _key: PhantomData,
+        }
+    }
+
+    /// How many values are in the map. Constant time.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the map holds no values.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether the key is current, meaning its value is still in the map.
+    pub fn contains_key(&self, key: K) -> bool {
+        self.occupant_for(key).is_some()
+    }
+
+    /// Removes every value, making every outstanding key stale. Slots are kept for reuse.
+    pub fn clear(&mut self) {
+        self.drain();
+    }
+
+    /// Removes every value, yielding each value and its key, oldest first. Like
+    /// [`Vec::drain`], dropping the iterator part way through still removes the rest.
+    pub fn drain(&mut self) -> Drain<'_, T, K, C> {
+        Drain::new(self)
+    }
+
+    /// Keeps only the values for which `f` returns `true`, removing the rest. Values are visited
+    /// oldest first.
+    pub fn retain(&mut self, mut f: impl FnMut(K, &mut T) -> bool) {
+        let mut next = self.head;
+        while let Some(index) = next {
+            next = self.occupant(index).next;
+            let key = self.key_at(index);
+            if !f(key, &mut self.occupant_mut(index).value) {
+                self.vacate(index, Departure::Removed);
             }
         }
     }
 
-    pub fn get(&self, idx: usize) -> Option<&T> {
-        Option::flatten(self.0.get(idx).map(Option::as_ref)).map(|o| &o.0)
+    /// The clock this map reads the time from.
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    /// The clock this map reads the time from. Clocks must never go backwards, and that includes
+    /// clocks changed through this reference.
+    pub fn clock_mut(&mut self) -> &mut C {
+        &mut self.clock
+    }
+
+    /// Adds a value to the map. The key returned can later be used to retrieve it. Once the value
+    /// has been removed, the key's index may be handed out again, but with a new generation.
+    ///
+    /// Vacant slots are kept on a free list, so this runs in constant time, or logarithmic time for
+    /// some [allocation strategies](Builder::allocation). If the map was built
+    /// with a [quarantine](Builder::quarantine_for), vacant slots are only reused once their
+    /// quarantine is over.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map is full. Use [`try_insert`](Self::try_insert) or
+    /// [`insert_evicting`](Self::insert_evicting) to handle that instead.
+    pub fn insert(&mut self, t: T) -> K {
+        let now = self.clock.now();
+        expect_room(self.insert_lease(t, now, None, None))
+    }
+
+    /// Adds a value to the map like [`insert`](Self::insert), but if the map is full, hands the
+    /// value back instead of panicking.
+    ///
+    /// The map is full once every slot is in use and no more can be added, either because it has
+    /// [`max_slots`](Builder::max_slots), or because the key type can't index another. Slots still
+    /// in [quarantine](Builder::quarantine_for) are in use for this purpose.
+    pub fn try_insert(&mut self, t: T) -> Result<K, CapacityError<T>> {
+        let now = self.clock.now();
+        self.insert_lease(t, now, None, None)
+    }
+
+    /// Adds a value to the map like [`insert`](Self::insert), but if the map is
+    /// [full](Self::try_insert), first evicts the oldest value to make room. Returns the new key,
+    /// and the key and value of whatever was evicted.
+    ///
+    /// The evicted value's slot is reused straight away, even if the map was built with a
+    /// [quarantine](Builder::quarantine_for), since its generation advances so the evicted key
+    /// won't match the new value. Keys without a [generation](LeaseKey::generation) would match
+    /// though, so if the map has a quarantine and such keys, nothing is evicted and the value is
+    /// handed back instead. It's also handed back if the map holds no values to evict, which can
+    /// only happen when every slot is in quarantine or [reserved](Self::reserve).
+    pub fn insert_evicting(&mut self, t: T) -> Evicting<T, K> {
+        self.insert_evicting_with(t, &mut OldestFirst)
+    }
+
+    /// Adds a value to the map like [`insert_evicting`](Self::insert_evicting), but `policy`
+    /// chooses which value is evicted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `policy` chooses a key which isn't current.
+    pub fn insert_evicting_with<P: EvictionPolicy<T, K, C> + ?Sized>(
+        &mut self,
+        t: T,
+        policy: &mut P,
+    ) -> Evicting<T, K> {
+        let t = match self.try_insert(t) {
+            Ok(key) => return Ok((key, None)),
+            Err(e) => e.into_inner(),
+        };
+        let Some(victim) = policy.choose(self) else {
+            return Err(CapacityError(t));
+        };
+        assert!(
+            self.contains_key(victim),
+            "eviction policy chose a key which isn't current"
+        );
+        if victim.generation().is_none() && self.has_quarantine() {
+            return Err(CapacityError(t));
+        }
+        let index = victim.index();
+        // The new value moves straight in, so the slot never goes on the free list or into
+        // quarantine.
+        let evicted = self.check_out(index).value;
+        let slot = &mut self.slots[index];
+        slot.generation = slot.generation.wrapping_add(1);
+        let now = self.clock.now();
+        let key = self.occupy(index, t, now, None, None);
+        Ok((key, Some((victim, evicted))))
+    }
+
+    /// Claims a slot before its value exists, such as when the key has to go inside the value.
+    /// The slot is filled with [`VacantLease::fill`], or released again if the lease is dropped.
+    /// Until then it isn't handed out for anything else, and sweeps pass over it.
+    ///
+    /// The lease borrows the map. To hold a slot in a shared map without keeping it locked, use
+    /// [`SharedVacantLease`], or [`ConcurrentShortLeaseMap::reserve`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map is [full](Self::try_insert). Use [`try_reserve`](Self::try_reserve) to
+    /// handle that instead.
+    pub fn reserve(&mut self) -> VacantLease<'_, T, K, C> {
+        self.try_reserve().expect("ShortLeaseMap is full")
     }
 
-    /// Removes the value with this index. The index may be assigned again after it has been removed.
-    pub fn remove(&mut self, idx: usize) -> Option<T> {
-        self.0.get_mut(idx).and_then(Option::take).map(|o| o.0)
+    /// Claims a slot like [`reserve`](Self::reserve), but returns `None` if the map is
+    /// [full](Self::try_insert).
+    pub fn try_reserve(&mut self) -> Option<VacantLease<'_, T, K, C>> {
+        let index = self.reserve_slot()?;
+        Some(VacantLease::new(self, index))
+    }
+
+    /// Adds a value to the map which expires once `ttl` has passed. See
+    /// [`dump_expired`](Self::dump_expired). If that's further off than the clock can represent,
+    /// such as with a `ttl` of [`Duration::MAX`](std::time::Duration::MAX), the value never
+    /// expires on its own.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map is [full](Self::try_insert).
+    pub fn insert_with_ttl(&mut self, t: T, ttl: C::Duration) -> K {
+        expect_room(self.try_insert_with_ttl(t, ttl))
+    }
+
+    /// Adds a value to the map which expires at `deadline`. See
+    /// [`dump_expired`](Self::dump_expired).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map is [full](Self::try_insert).
+    pub fn insert_with_deadline(&mut self, t: T, deadline: C::Instant) -> K {
+        let now = self.clock.now();
+        expect_room(self.insert_lease(t, now, Some(deadline), None))
+    }
+
+    /// Gets the value for this key. Returns `None` if the key is stale, meaning the value it
+    /// referred to has since been removed.
+    pub fn get(&self, key: K) -> Option<&T> {
+        self.occupant_for(key).map(|o| &o.value)
+    }
+
+    /// Gets the value for this key mutably. If the map was built with
+    /// [`sliding`](Builder::sliding) leases, this also [renews](Self::renew) the lease.
+    pub fn get_mut(&mut self, key: K) -> Option<&mut T> {
+        if self.sliding {
+            self.renew(key);
+        }
+        self.slots
+            .get_mut(key.index())
+            .filter(|s| s.get(key.generation()).is_some())
+            .and_then(|s| s.occupant.as_mut())
+            .map(|o| &mut o.value)
+    }
+
+    /// Resets the lease for this key as if its value was inserted just now, moving it to the back
+    /// of the eviction order. A value inserted with a TTL also gets a fresh deadline that far from
+    /// now. Returns `false` if the key is stale.
+    pub fn renew(&mut self, key: K) -> bool {
+        if self.occupant_for(key).is_none() {
+            return false;
+        }
+        let now = self.clock.now();
+        self.unlink(key.index());
+        self.link_back(key.index());
+        let occupant = self.occupant_mut(key.index());
+        occupant.inserted_at = now;
+        if let Some(ttl) = occupant.ttl {
+            self.set_deadline(key.index(), C::checked_add(now, ttl));
+        }
+        true
+    }
+
+    /// Pushes back the deadline for this key by `by`, returning the new deadline. Returns `None`,
+    /// leaving the deadline as it was, if the key is stale, its value has no deadline of its own,
+    /// or the new deadline is further off than the clock can represent.
+    pub fn extend(&mut self, key: K, by: C::Duration) -> Option<C::Instant> {
+        let deadline = C::checked_add(self.occupant_for(key)?.deadline?, by)?;
+        self.set_deadline(key.index(), Some(deadline));
+        Some(deadline)
+    }
+
+    /// When the value for this key was inserted, or last [renewed](Self::renew).
+    pub fn inserted_at(&self, key: K) -> Option<C::Instant> {
+        self.occupant_for(key).map(|o| o.inserted_at)
+    }
+
+    /// How long the value for this key has been in the map, since it was inserted or last
+    /// [renewed](Self::renew).
+    pub fn age(&self, key: K) -> Option<C::Duration> {
+        let inserted_at = self.inserted_at(key)?;
+        Some(C::duration_since(self.clock.now(), inserted_at))
+    }
+
+    /// How long until the value for this key is older than `max_age`, or zero if it already is.
+    /// If `max_age` is so long that the clock can't represent the time it ends at, this saturates
+    /// at `max_age`.
+    pub fn remaining(&self, key: K, max_age: C::Duration) -> Option<C::Duration> {
+        let inserted_at = self.inserted_at(key)?;
+        let now = self.clock.now();
+        // Values inserted after the cutoff are younger than `max_age`, by as much as they were
+        // inserted after it.
+        Some(match C::checked_sub(now, max_age) {
+            Some(cutoff) => C::duration_since(inserted_at, cutoff),
+            None => match C::checked_add(inserted_at, max_age) {
+                Some(expires_at) => C::duration_since(expires_at, now),
+                None => max_age,
+            },
+        })
+    }
+
+    /// The deadline of the value for this key, if it was given one.
+    pub fn deadline(&self, key: K) -> Option<C::Instant> {
+        self.occupant_for(key)?.deadline
+    }
+
+    /// The longest staying value, with its key and when it was inserted. This is the next value
+    /// [`dump_old_values`](Self::dump_old_values) will evict.
+    pub fn oldest(&self) -> Option<(K, &T, C::Instant)> {
+        let head = self.head?;
+        let occupant = self.occupant(head);
+        Some((self.key_at(head), &occupant.value, occupant.inserted_at))
+    }
+
+    /// The first instant at which the oldest value is older than `max_age`, so a caller can sleep
+    /// until then before calling [`dump_old_values`](Self::dump_old_values), which will evict it.
+    /// Returns `None` if the map is empty, or if that time is further off than the clock can
+    /// represent, since then nothing will ever be old enough.
+    pub fn next_expiry(&self, max_age: C::Duration) -> Option<C::Instant> {
+        let (_, _, inserted_at) = self.oldest()?;
+        // Sweeps only evict values strictly older than `max_age`.
+        C::checked_add(C::checked_add(inserted_at, max_age)?, C::RESOLUTION)
+    }
+
+    /// The soonest deadline of any value, so a caller can sleep until then before calling
+    /// [`dump_expired`](Self::dump_expired). Returns `None` if no value has a deadline.
+    pub fn next_deadline(&self) -> Option<C::Instant> {
+        self.deadlines.first().map(|&(deadline, _)| deadline)
+    }
+
+    /// Finds out what became of the value for this key. Unlike [`get`](Self::get), this tells a
+    /// key that was never handed out apart from one whose value is gone, and if the map keeps
+    /// [tombstones](Builder::tombstones), whether that value was removed or expired, and when.
+    pub fn lookup(&self, key: K) -> Lookup<'_, T, C::Instant> {
+        let Some(slot) = self.slots.get(key.index()) else {
+            return Lookup::NeverIssued;
+        };
+        if let Some(occupant) = slot.get(key.generation()) {
+            return Lookup::Occupied(&occupant.value);
+        }
+        if slot.reserved && key.generation().is_none_or(|g| g == slot.generation) {
+            return Lookup::Reserved;
+        }
+        // A vacant slot's generation is the one its next occupant will get, so only generations
+        // before it have been handed out.
+        let issued_until = match slot.occupant {
+            Some(_) => slot.generation,
+            None => slot.generation.wrapping_sub(1),
+        };
+        // Keys without a generation refer to the slot's most recent occupant.
+        let generation = key.generation().unwrap_or(issued_until);
+        // Generations wrap around, so one counts as handed out if it's less than half the range
+        // behind the latest. A slot which has never been occupied has issued none at all.
+        if issued_until.wrapping_sub(generation) >= u32::MAX / 2 {
+            return Lookup::NeverIssued;
+        }
+        match slot.tombstone {
+            Some(Tombstone { departure, at }) if generation == issued_until => match departure {
+                Departure::Removed => Lookup::Removed { at },
+                Departure::Expired => Lookup::Expired { at },
+            },
+            _ => Lookup::Stale,
+        }
+    }
+
+    /// Removes the value with this key. The key's index may be assigned again after it has been
+    /// removed, but this key will not match the new value.
+    pub fn remove(&mut self, key: K) -> Option<T> {
+        self.occupant_for(key)?;
+        Some(self.vacate(key.index(), Departure::Removed).value)
+    }
+
+    /// Removes the value with this key, but only if `pred` returns `true` for it.
+    pub fn remove_if(&mut self, key: K, pred: impl FnOnce(&T) -> bool) -> Option<T> {
+        if !pred(self.get(key)?) {
+            return None;
+        }
+        self.remove(key)
+    }
+
+    /// Replaces the value with this key, returning the old value. The lease is left as it was.
+    /// If the key is stale, `t` is handed back instead.
+    pub fn replace(&mut self, key: K, t: T) -> Result<T, T> {
+        match self.slots.get_mut(key.index()) {
+            Some(slot) if slot.get(key.generation()).is_some() => {
+                let occupant = slot.occupant.as_mut().expect("slot must be occupied");
+                Ok(std::mem::replace(&mut occupant.value, t))
+            }
+            _ => Err(t),
+        }
+    }
+
+    /// Gets the slot this key refers to, for in place updates.
+    ///
+    /// If the key is current, this is an [`Entry::Occupied`]. If its slot is vacant, this is an
+    /// [`Entry::Vacant`], which can put a value in that particular slot. Returns `None` if the slot
+    /// has never been handed out, or has a new occupant since this key was handed out.
+    pub fn entry(&mut self, key: K) -> Option<Entry<'_, T, K, C>> {
+        let index = key.index();
+        let slot = self.slots.get(index)?;
+        if slot.get(key.generation()).is_some() {
+            Some(Entry::Occupied(OccupiedEntry::new(self, index)))
+        } else if slot.occupant.is_none() && !slot.reserved {
+            Some(Entry::Vacant(VacantEntry::new(self, index)))
+        } else {
+            None
+        }
     }
 
     /// Evict guests which have overstayed their welcome. If a value has been in the map longer than
     /// the `max_age` given, it will be dropped. Returns a count of how many items were removed.
     ///
-    // Clippy will suggest we simplify this code by using Iterator::flatten. It is wrong, the code
-    // is not able to change the iterated value to `None` while using Iterator::flatten.
-    #[allow(clippy::manual_flatten)]
-    pub fn dump_old_values(&mut self, max_age: Duration) -> usize {
-        let mut total_dumped = 0;
-        for e in &mut self.0 {
-            if let Some((_, insert_time)) = e {
-                if insert_time.elapsed() > max_age {
-                    *e = None;
-                    total_dumped += 1;
-                }
-            }
+    /// Guests are checked in the order they arrived, stopping at the first one still welcome, so
+    /// this only costs time proportional to the number of guests evicted. Values with their own
+    /// deadline are subject to `max_age` as well.
+    pub fn dump_old_values(&mut self, max_age: C::Duration) -> usize {
+        self.drain_expired(max_age).count()
+    }
+
+    /// Like [`dump_old_values`](Self::dump_old_values), but hands back each evicted value along
+    /// with its key and the time it was inserted, oldest first.
+    ///
+    /// If the iterator is dropped before it is exhausted, the remaining expired values are still
+    /// evicted, just as with `Vec::drain`.
+    pub fn drain_expired(&mut self, max_age: C::Duration) -> DrainExpired<'_, T, K, C> {
+        DrainExpired {
+            now: self.clock.now(),
+            map: self,
+            sweep: Sweep::MaxAge(max_age),
+        }
+    }
+
+    /// Evict guests whose own deadline has been reached, as given to
+    /// [`insert_with_ttl`](Self::insert_with_ttl) or
+    /// [`insert_with_deadline`](Self::insert_with_deadline). Values inserted without a deadline
+    /// are left alone. Returns a count of how many items were removed.
+    pub fn dump_expired(&mut self) -> usize {
+        self.drain_overdue().count()
+    }
+
+    /// Like [`dump_expired`](Self::dump_expired), but hands back each evicted value along with its
+    /// key and the time it was inserted, soonest deadline first.
+    ///
+    /// If the iterator is dropped before it is exhausted, the remaining overdue values are still
+    /// evicted.
+    pub fn drain_overdue(&mut self) -> DrainExpired<'_, T, K, C> {
+        DrainExpired {
+            now: self.clock.now(),
+            map: self,
+            sweep: Sweep::Deadline,
         }
-        total_dumped
     }
 
     /// Iterates immutably over the collection, returning a tuple of a reference to the item and its
-    /// ID value.
-    pub fn iter(&self) -> impl Iterator<Item = (&T, usize)> + DoubleEndedIterator {
-        self.0
-            .iter()
-            .enumerate()
-            .filter_map(|(i, e)| (e.as_ref().map(|o| (&o.0, i))))
+    /// key.
+    pub fn iter(&self) -> Iter<'_, T, K, C> {
+        Iter::new(&self.slots, self.len)
     }
 
     /// Iterates mutably over the collection, returning a tuple of a mutable reference to the item
-    /// and its ID value.
-    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&mut T, usize)> + DoubleEndedIterator {
-        self.0
-            .iter_mut()
-            .enumerate()
-            .filter_map(|(i, e)| e.as_mut().map(|o| (&mut o.0, i)))
+    /// and its key.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T, K, C> {
+        IterMut::new(&mut self.slots, self.len)
+    }
+
+    /// Iterates immutably over the collection, returning a tuple of each item's key, a reference
+    /// to the item, and when it was inserted.
+    pub fn iter_leases(&self) -> Leases<'_, T, K, C> {
+        Leases::new(&self.slots, self.len)
+    }
+
+    /// Iterates mutably over the collection, returning a tuple of each item's key, a mutable
+    /// reference to the item, and when it was inserted.
+    pub fn iter_leases_mut(&mut self) -> LeasesMut<'_, T, K, C> {
+        LeasesMut::new(&mut self.slots, self.len)
+    }
+
+    /// Evicts the oldest value if it was older than `max_age` at `now`.
+    fn pop_older_than(
+        &mut self,
+        max_age: C::Duration,
+        now: C::Instant,
+    ) -> Option<(K, T, C::Instant)> {
+        let head = self.head?;
+        if C::duration_since(now, self.occupant(head).inserted_at) <= max_age {
+            return None;
+        }
+        Some(self.evict(head))
+    }
+
+    /// Evicts the value with the soonest deadline if that deadline had been reached at `now`.
+    pub(crate) fn pop_overdue(&mut self, now: C::Instant) -> Option<(K, T, C::Instant)> {
+        let &(deadline, index) = self.deadlines.first()?;
+        if deadline > now {
+            return None;
+        }
+        Some(self.evict(index))
+    }
+
+    /// Vacates an occupied slot, returning its key, value and insertion time.
+    fn evict(&mut self, index: usize) -> (K, T, C::Instant) {
+        let key = self.key_at(index);
+        let occupant = self.vacate(index, Departure::Expired);
+        (key, occupant.value, occupant.inserted_at)
+    }
+
+    /// The occupant this key refers to, unless the key is stale.
+    fn occupant_for(&self, key: K) -> Option<&Occupant<T, C>> {
+        self.slots.get(key.index())?.get(key.generation())
+    }
+
+    /// Adds a value like [`insert_with_ttl`](Self::insert_with_ttl), but hands it back if the map
+    /// is full.
+    fn try_insert_with_ttl(&mut self, t: T, ttl: C::Duration) -> Result<K, CapacityError<T>> {
+        let now = self.clock.now();
+        self.insert_lease(t, now, C::checked_add(now, ttl), Some(ttl))
+    }
+
+    /// Checks in a new occupant at the back of the check in order.
+    fn insert_lease(
+        &mut self,
+        t: T,
+        now: C::Instant,
+        deadline: Option<C::Instant>,
+        ttl: Option<C::Duration>,
+    ) -> Result<K, CapacityError<T>> {
+        match self.take_slot(now) {
+            Some(index) => Ok(self.occupy(index, t, now, deadline, ttl)),
+            None => Err(CapacityError(t)),
+        }
+    }
+
+    /// Takes the next vacant slot off the free list, or adds a new one. Returns `None` if the map
+    /// is full.
+    fn take_slot(&mut self, now: C::Instant) -> Option<usize> {
+        self.release_quarantined(now);
+        if let Some(index) = self.free.pop() {
+            return Some(index);
+        }
+        if self.slots.len() >= self.max_slots {
+            return None;
+        }
+        self.slots.push(Slot {
+            generation: 0,
+            occupant: None,
+            reserved: false,
+            tombstone: None,
+        });
+        self.free.grew(self.slots.len() - 1);
+        Some(self.slots.len() - 1)
+    }
+
+    /// Checks a value in to a vacant slot which has already been taken off the free list.
+    fn occupy(
+        &mut self,
+        index: usize,
+        t: T,
+        now: C::Instant,
+        deadline: Option<C::Instant>,
+        ttl: Option<C::Duration>,
+    ) -> K {
+        self.allocations += 1;
+        self.len += 1;
+        let slot = &mut self.slots[index];
+        slot.tombstone = None;
+        slot.occupant = Some(Occupant {
+            value: t,
+            inserted_at: now,
+            deadline: None,
+            ttl,
+            prev: None,
+            next: None,
+        });
+        self.link_back(index);
+        self.set_deadline(index, deadline);
+        self.key_at(index)
+    }
+
+    /// Takes a particular vacant slot off the free list, or out of quarantine.
+    fn claim(&mut self, index: usize) {
+        match self.quarantine.iter().position(|&(i, ..)| i == index) {
+            Some(position) => {
+                self.quarantine.remove(position);
+            }
+            None => self.free.remove(index),
+        }
+    }
+
+    /// Takes a vacant slot and holds it for a value which doesn't exist yet. Other inserts pass it
+    /// over until it's [filled](Self::fill_reserved) or [released](Self::release_reserved).
+    fn reserve_slot(&mut self) -> Option<usize> {
+        let now = self.clock.now();
+        let index = self.take_slot(now)?;
+        self.slots[index].reserved = true;
+        Some(index)
+    }
+
+    /// Puts a value in a slot held by [`reserve_slot`](Self::reserve_slot). The lease starts now.
+    fn fill_reserved(&mut self, index: usize, t: T, ttl: Option<C::Duration>) -> K {
+        debug_assert!(self.slots[index].reserved, "filled slot must be reserved");
+        self.slots[index].reserved = false;
+        let now = self.clock.now();
+        let deadline = ttl.and_then(|ttl| C::checked_add(now, ttl));
+        self.occupy(index, t, now, deadline, ttl)
+    }
+
+    /// Gives up a slot held by [`reserve_slot`](Self::reserve_slot), as if its value had been
+    /// removed.
+    fn release_reserved(&mut self, index: usize) {
+        debug_assert!(self.slots[index].reserved, "released slot must be reserved");
+        self.slots[index].reserved = false;
+        self.retire(index, Departure::Removed);
+    }
+
+    /// Whether vacated slots go into quarantine before they're reused.
+    fn has_quarantine(&self) -> bool {
+        self.quarantine_time.is_some() || self.quarantine_allocations > 0
+    }
+
+    /// Moves slots whose quarantine is over onto the free list.
+    fn release_quarantined(&mut self, now: C::Instant) {
+        while let Some(&(index, vacated_at, allocations)) = self.quarantine.front() {
+            let waited_out_time = self
+                .quarantine_time
+                .is_none_or(|time| C::duration_since(now, vacated_at) >= time);
+            let waited_out_allocations =
+                self.allocations - allocations >= self.quarantine_allocations;
+            if !(waited_out_time && waited_out_allocations) {
+                break;
+            }
+            self.quarantine.pop_front();
+            self.free.push(index);
+        }
+    }
+
+    /// Links an occupied slot in at the back of the check in order.
+    fn link_back(&mut self, index: usize) {
+        let tail = self.tail;
+        let occupant = self.occupant_mut(index);
+        occupant.prev = tail;
+        occupant.next = None;
+        match tail {
+            Some(tail) => self.occupant_mut(tail).next = Some(index),
+            None => self.head = Some(index),
+        }
+        self.tail = Some(index);
+    }
+
+    /// Unlinks an occupied slot from the check in order.
+    fn unlink(&mut self, index: usize) {
+        let Occupant { prev, next, .. } = *self.occupant(index);
+        match prev {
+            Some(prev) => self.occupant_mut(prev).next = next,
+            None => self.head = next,
+        }
+        match next {
+            Some(next) => self.occupant_mut(next).prev = prev,
+            None => self.tail = prev,
+        }
+    }
+
+    /// Replaces the deadline of an occupied slot, keeping the deadline order up to date.
+    fn set_deadline(&mut self, index: usize, deadline: Option<C::Instant>) {
+        let occupant = self.occupant_mut(index);
+        let old = std::mem::replace(&mut occupant.deadline, deadline);
+        if let Some(old) = old {
+            self.deadlines.remove(&(old, index));
+        }
+        if let Some(deadline) = deadline {
+            self.deadlines.insert((deadline, index));
+        }
+    }
+
+    /// The key for a slot known to be occupied.
+    fn key_at(&self, index: usize) -> K {
+        K::from_parts(index, self.slots[index].generation)
+    }
+
+    /// The occupant of a slot known to be occupied.
+    fn occupant(&self, index: usize) -> &Occupant<T, C> {
+        self.slots[index]
+            .occupant
+            .as_ref()
+            .expect("linked slot must be occupied")
+    }
+
+    /// The occupant of a slot known to be occupied.
+    fn occupant_mut(&mut self, index: usize) -> &mut Occupant<T, C> {
+        self.slots[index]
+            .occupant
+            .as_mut()
+            .expect("linked slot must be occupied")
+    }
+
+    /// [Checks out](Self::check_out) the occupant of a slot known to be occupied, then
+    /// [retires](Self::retire) the slot.
+    fn vacate(&mut self, index: usize, departure: Departure) -> Occupant<T, C> {
+        let occupant = self.check_out(index);
+        self.retire(index, departure);
+        occupant
+    }
+
+    /// Takes the occupant out of a slot known to be occupied, leaving the slot's generation as it
+    /// was.
+    fn check_out(&mut self, index: usize) -> Occupant<T, C> {
+        self.unlink(index);
+        self.set_deadline(index, None);
+        let occupant = self.slots[index]
+            .occupant
+            .take()
+            .expect("vacated slot must be occupied");
+        self.len -= 1;
+        occupant
+    }
+
+    /// Advances the generation of a slot which has just become vacant, so outstanding keys become
+    /// stale, and puts it on the free list, or into quarantine.
+    fn retire(&mut self, index: usize, departure: Departure) {
+        let quarantined = self.has_quarantine();
+        let now = (quarantined || self.tombstones).then(|| self.clock.now());
+        let slot = &mut self.slots[index];
+        slot.generation = slot.generation.wrapping_add(1);
+        if self.tombstones {
+            slot.tombstone = now.map(|at| Tombstone { departure, at });
+        }
+        match now {
+            Some(now) if quarantined => self.quarantine.push_back((index, now, self.allocations)),
+            _ => self.free.push(index),
+        }
+    }
+}
+
+/// An iterator over the values evicted by [`ShortLeaseMap::drain_expired`] or
+/// [`ShortLeaseMap::drain_overdue`], yielding each value's key, the value, and the time it was
+/// inserted.
+#[derive(Debug)]
+pub struct DrainExpired<'a, T, K: LeaseKey = Key, C: Clock = SystemClock> {
+    map: &'a mut ShortLeaseMap<T, K, C>,
+    now: C::Instant,
+    sweep: Sweep<C::Duration>,
+}
+
+#[derive(Debug)]
+enum Sweep<D> {
+    /// Everything older than this, in check in order.
+    MaxAge(D),
+    /// Everything whose own deadline has been reached, in deadline order.
+    Deadline,
+}
+
+impl<T, K: LeaseKey, C: Clock> Iterator for DrainExpired<'_, T, K, C> {
+    type Item = (K, T, C::Instant);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.sweep {
+            Sweep::MaxAge(max_age) => self.map.pop_older_than(max_age, self.now),
+            Sweep::Deadline => self.map.pop_overdue(self.now),
+        }
     }
 }
 
-impl<T> Default for ShortLeaseMap<T> {
+impl<T, K: LeaseKey, C: Clock> Drop for DrainExpired<'_, T, K, C> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
+
+impl<T, K: LeaseKey, C: Clock + Default> Default for ShortLeaseMap<T, K, C> {
     fn default() -> Self {
-        Self(Vec::default())
+        Builder::with_clock(C::default()).keys().build()
+    }
+}
+
+impl<T, K: LeaseKey, C: Clock> Index<K> for ShortLeaseMap<T, K, C> {
+    type Output = T;
+
+    /// Gets the value for this key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is stale.
+    fn index(&self, key: K) -> &T {
+        self.get(key).expect("stale key")
+    }
+}
+
+impl<T, K: LeaseKey, C: Clock> IndexMut<K> for ShortLeaseMap<T, K, C> {
+    /// Gets the value for this key mutably, as [`get_mut`](ShortLeaseMap::get_mut) does.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is stale.
+    fn index_mut(&mut self, key: K) -> &mut T {
+        self.get_mut(key).expect("stale key")
+    }
+}
+
+/// Maps are equal if they hold equal values under the same keys. Leases and settings aren't
+/// compared.
+impl<T: PartialEq, K: LeaseKey, C: Clock> PartialEq for ShortLeaseMap<T, K, C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().all(|(t, key)| other.get(key) == Some(t))
+    }
+}
+
+impl<T: Eq, K: LeaseKey, C: Clock> Eq for ShortLeaseMap<T, K, C> {}
+
+impl<T, K: LeaseKey, C: Clock + Default> FromIterator<T> for ShortLeaseMap<T, K, C> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut map = Self::default();
+        Extend::extend(&mut map, iter);
+        map
+    }
+}
+
+/// Inserts each value, discarding the keys. Panics if the map runs out of keys, as
+/// [`insert`](ShortLeaseMap::insert) does.
+impl<T, K: LeaseKey, C: Clock> Extend<T> for ShortLeaseMap<T, K, C> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for t in iter {
+            self.insert(t);
+        }
+    }
+}
+
+/// The error from [`ShortLeaseMap::try_insert`] when the map is full. The value that could not be
+/// inserted is handed back.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct CapacityError<T>(T);
+
+impl<T> CapacityError<T> {
+    /// The value that could not be inserted.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> fmt::Debug for CapacityError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CapacityError").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Display for CapacityError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("every slot is in use")
+    }
+}
+
+impl<T> Error for CapacityError<T> {}
+
+/// The new key and whatever was evicted for it, from [`ShortLeaseMap::insert_evicting`].
+type Evicting<T, K> = Result<(K, Option<(K, T)>), CapacityError<T>>;
+
+fn expect_room<K, T>(inserted: Result<K, CapacityError<T>>) -> K {
+    match inserted {
+        Ok(key) => key,
+        Err(e) => panic!("ShortLeaseMap is full: {e}"),
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::time::{Duration, Instant};
+
     use super::*;
 
     #[test]
     fn short_lease_map() {
         const CAPACITY: usize = 10;
         let mut map = ShortLeaseMap::with_capacity(CAPACITY);
-        assert_eq!(map.0.capacity(), CAPACITY);
-        for i in 0..CAPACITY + 1 {
-            assert_eq!(map.insert(i), i);
-        }
-        assert_eq!(map.remove(3), Some(3));
-        assert_eq!(map.insert(0), 3);
-        assert_eq!(map.insert(5), CAPACITY + 1);
-        assert_eq!(map.remove(3), Some(0));
-        assert_eq!(map.insert(0), 3);
+        assert_eq!(map.slots.capacity(), CAPACITY);
+        let keys = (0..CAPACITY + 1).map(|i| map.insert(i)).collect::<Vec<_>>();
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(key.index(), i);
+        }
+        assert_eq!(map.remove(keys[3]), Some(3));
+        let reused = map.insert(0);
+        assert_eq!(reused.index(), 3);
+        assert_eq!(map.insert(5).index(), CAPACITY + 1);
+        assert_eq!(map.remove(reused), Some(0));
+        assert_eq!(map.insert(0).index(), 3);
+    }
+
+    #[test]
+    fn stale_keys() {
+        let clock = ManualClock::new();
+        let mut map = ShortLeaseMap::with_clock(clock.clone());
+        let old = map.insert("request");
+        assert_eq!(map.remove(old), Some("request"));
+        let new = map.insert("other request");
+        assert_eq!(old.index(), new.index());
+        assert_ne!(old, new);
+        assert_eq!(map.get(old), None);
+        assert_eq!(map.remove(old), None);
+        assert_eq!(map.get(new), Some(&"other request"));
+        assert_eq!(
+            map.iter().collect::<Vec<_>>(),
+            vec![(&"other request", new)]
+        );
+
+        clock.advance(Duration::from_secs(1));
+        map.dump_old_values(Duration::ZERO);
+        assert_eq!(map.get(new), None);
+        assert_ne!(map.insert("third request"), new);
+    }
+
+    #[test]
+    fn dump_old_values_stops_at_young_entries() {
+        let clock = ManualClock::new();
+        let mut map = ShortLeaseMap::with_clock(clock.clone());
+        let old = (0..3).map(|i| map.insert(i)).collect::<Vec<_>>();
+        map.remove(old[1]);
+        clock.advance(Duration::from_millis(20));
+        let young = map.insert(3);
+        assert_eq!(map.dump_old_values(Duration::from_millis(20)), 0);
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(map.dump_old_values(Duration::from_millis(20)), 2);
+        assert_eq!(map.iter().collect::<Vec<_>>(), vec![(&3, young)]);
+        assert_eq!(map.head, Some(young.index()));
+        assert_eq!(map.tail, Some(young.index()));
+    }
+
+    #[test]
+    fn drain_expired() {
+        let clock = ManualClock::new();
+        let mut map = ShortLeaseMap::with_clock(clock.clone());
+        let keys = (0..3).map(|i| map.insert(i)).collect::<Vec<_>>();
+        clock.advance(Duration::from_secs(1));
+        let mut drain = map.drain_expired(Duration::ZERO);
+        let (key, value, _) = drain.next().unwrap();
+        assert_eq!((key, value), (keys[0], 0));
+        drop(drain);
+        assert_eq!(map.iter().count(), 0);
+        assert_eq!(map.drain_expired(Duration::ZERO).count(), 0);
+    }
+
+    #[test]
+    fn per_entry_deadlines() {
+        let clock = ManualClock::new();
+        let mut map = ShortLeaseMap::with_clock(clock.clone());
+        let forever = map.insert("no deadline");
+        let slow = map.insert_with_ttl("bulk rpc", Duration::from_secs(60));
+        let fast = map.insert_with_ttl("dns lookup", Duration::from_secs(2));
+        let faster = map.insert_with_deadline("cache probe", clock.now());
+        assert_eq!(
+            map.drain_overdue().map(|(k, _, _)| k).collect::<Vec<_>>(),
+            vec![faster]
+        );
+        clock.advance(Duration::from_secs(2));
+        let overdue = map
+            .drain_overdue()
+            .map(|(k, v, _)| (k, v))
+            .collect::<Vec<_>>();
+        assert_eq!(overdue, vec![(fast, "dns lookup")]);
+        assert_eq!(map.dump_expired(), 0);
+        assert_eq!(map.get(forever), Some(&"no deadline"));
+        assert_eq!(map.remove(slow), Some("bulk rpc"));
+        assert!(map.deadlines.is_empty());
+    }
+
+    #[test]
+    fn ticks() {
+        let mut map = ShortLeaseMap::with_ticks();
+        let first = map.insert("frame 0 input");
+        map.set_tick(3);
+        let second = map.insert_with_ttl("frame 3 input", 2);
+        map.advance_ticks(2);
+        assert_eq!(map.current_tick(), 5);
+        assert_eq!(map.dump_older_than_ticks(5), 0);
+        assert_eq!(map.dump_expired(), 1);
+        assert_eq!(map.get(second), None);
+        map.advance_ticks(1);
+        assert_eq!(map.dump_older_than_ticks(5), 1);
+        assert_eq!(map.get(first), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn ticks_never_go_backwards() {
+        let mut map = ShortLeaseMap::<(), Key, TickClock>::with_ticks();
+        map.set_tick(3);
+        map.set_tick(2);
+    }
+
+    #[test]
+    fn renew_and_extend() {
+        let mut map = ShortLeaseMap::with_ticks();
+        let long_poll = map.insert("long poll");
+        let stream = map.insert_with_ttl("stream", 5);
+        let idle = map.insert("idle");
+        map.advance_ticks(4);
+        assert!(map.renew(long_poll));
+        assert!(map.renew(stream));
+        assert_eq!(map.extend(stream, 2), Some(11));
+        assert_eq!(map.extend(long_poll, 2), None);
+        assert_eq!(map.extend(stream, u64::MAX), None);
+        assert_eq!(map.deadline(stream), Some(11));
+        map.advance_ticks(4);
+        assert_eq!(
+            map.drain_expired(5).map(|(k, _, _)| k).collect::<Vec<_>>(),
+            vec![idle]
+        );
+        map.advance_ticks(3);
+        assert_eq!(map.dump_expired(), 1);
+        assert_eq!(map.get(stream), None);
+        assert_eq!(map.head, Some(long_poll.index()));
+        assert_eq!(map.tail, Some(long_poll.index()));
+        assert!(!map.renew(stream));
+    }
+
+    #[test]
+    fn sliding() {
+        let mut map = Builder::with_clock(TickClock::new(0)).sliding(true).build();
+        let key = map.insert(0);
+        map.advance_ticks(4);
+        *map.get_mut(key).unwrap() += 1;
+        map.advance_ticks(4);
+        assert_eq!(map.dump_older_than_ticks(5), 0);
+        assert_eq!(map.get(key), Some(&1));
+        map.advance_ticks(2);
+        assert_eq!(map.dump_older_than_ticks(5), 1);
+    }
+
+    #[test]
+    fn lease_metadata() {
+        let mut map = ShortLeaseMap::with_ticks();
+        map.set_tick(10);
+        let key = map.insert_with_ttl("request", 8);
+        map.advance_ticks(3);
+        assert_eq!(map.inserted_at(key), Some(10));
+        assert_eq!(map.age(key), Some(3));
+        assert_eq!(map.remaining(key, 5), Some(2));
+        assert_eq!(map.remaining(key, 1), Some(0));
+        assert_eq!(map.remaining(key, 20), Some(17));
+        assert_eq!(map.remaining(key, u64::MAX), Some(u64::MAX));
+        assert_eq!(map.deadline(key), Some(18));
+        assert_eq!(
+            map.iter_leases().collect::<Vec<_>>(),
+            vec![(key, &"request", 10)]
+        );
+        assert_eq!(map.iter_leases().len(), 1);
+        assert_eq!(map.iter_leases_mut().len(), 1);
+        for (_, value, _) in map.iter_leases_mut() {
+            *value = "response";
+        }
+        assert_eq!(map.remove(key), Some("response"));
+        assert_eq!(map.age(key), None);
+        assert_eq!(map.remaining(key, 5), None);
+    }
+
+    #[test]
+    fn next_expiry() {
+        let mut map = ShortLeaseMap::with_ticks();
+        assert_eq!(map.oldest(), None);
+        assert_eq!(map.next_expiry(5), None);
+        let first = map.insert("first");
+        map.advance_ticks(2);
+        let second = map.insert_with_ttl("second", 4);
+        map.insert_with_deadline("third", 3);
+        assert_eq!(map.oldest(), Some((first, &"first", 0)));
+        assert_eq!(map.next_expiry(5), Some(6));
+        assert_eq!(map.next_expiry(u64::MAX - 1), Some(u64::MAX));
+        assert_eq!(map.next_expiry(u64::MAX), None);
+        assert_eq!(map.next_deadline(), Some(3));
+        map.remove(first);
+        assert_eq!(map.oldest(), Some((second, &"second", 2)));
+        assert_eq!(map.next_expiry(5), Some(8));
+        assert_eq!(map.next_expiry(u64::MAX), None);
+
+        map.set_tick(7);
+        assert_eq!(map.dump_old_values(5), 0);
+        map.set_tick(map.next_expiry(5).unwrap());
+        assert_eq!(map.dump_old_values(5), 2);
+        assert_eq!(map.get(second), None);
+
+        let clock = ManualClock::new();
+        let mut map = ShortLeaseMap::with_clock(clock.clone());
+        map.insert("first");
+        let expiry = map.next_expiry(Duration::from_secs(5)).unwrap();
+        clock.advance(expiry - clock.now() - Duration::from_nanos(1));
+        assert_eq!(map.dump_old_values(Duration::from_secs(5)), 0);
+        clock.advance(expiry - clock.now());
+        assert_eq!(map.dump_old_values(Duration::from_secs(5)), 1);
+    }
+
+    #[test]
+    fn quarantine() {
+        let mut map = Builder::with_clock(TickClock::new(0))
+            .quarantine_for(10)
+            .build();
+        let first = map.insert("first");
+        map.remove(first);
+        assert_ne!(map.insert("second").index(), first.index());
+        map.advance_ticks(10);
+        assert_eq!(map.insert("third").index(), first.index());
+
+        let mut map = Builder::new().quarantine_allocations(2).build();
+        let first = map.insert("first");
+        map.remove(first);
+        assert_eq!(map.insert("second").index(), 1);
+        assert_eq!(map.insert("third").index(), 2);
+        assert_eq!(map.insert("fourth").index(), first.index());
+    }
+
+    #[test]
+    fn allocation_strategies() {
+        let order = |strategy| {
+            let mut map = Builder::new().allocation(strategy).build();
+            let keys = (0..5).map(|i| map.insert(i)).collect::<Vec<_>>();
+            for i in [3, 1, 2] {
+                map.remove(keys[i]);
+            }
+            (0..4).map(|i| map.insert(i).index()).collect::<Vec<_>>()
+        };
+        assert_eq!(order(AllocationStrategy::Lifo), vec![2, 1, 3, 5]);
+        assert_eq!(order(AllocationStrategy::Fifo), vec![3, 1, 2, 5]);
+        assert_eq!(order(AllocationStrategy::LowestFirst), vec![1, 2, 3, 5]);
+        assert_eq!(order(AllocationStrategy::RoundRobin), vec![1, 2, 3, 5]);
+
+        let mut map = Builder::new()
+            .allocation(AllocationStrategy::RoundRobin)
+            .build();
+        let keys = (0..4).map(|i| map.insert(i)).collect::<Vec<_>>();
+        map.remove(keys[1]);
+        map.remove(keys[3]);
+        assert_eq!(map.insert(4).index(), 1);
+        map.remove(keys[0]);
+        assert_eq!(map.insert(5).index(), 3);
+        assert_eq!(map.insert(6).index(), 0);
+        assert_eq!(map.insert(7).index(), 4);
+    }
+
+    #[test]
+    fn lookup() {
+        let mut map = Builder::with_clock(TickClock::new(0))
+            .tombstones(true)
+            .build();
+        let removed = map.insert("removed");
+        let expired = map.insert_with_ttl("expired", 1);
+        assert_eq!(map.lookup(removed), Lookup::Occupied(&"removed"));
+        map.advance_ticks(1);
+        map.dump_expired();
+        map.remove(removed);
+        assert_eq!(map.lookup(removed), Lookup::Removed { at: 1 });
+        assert_eq!(map.lookup(expired), Lookup::Expired { at: 1 });
+        let next = Key::from_parts(removed.index(), removed.generation() + 1);
+        assert_eq!(map.lookup(next), Lookup::NeverIssued);
+        assert_eq!(map.lookup(Key::from_parts(2, 0)), Lookup::NeverIssued);
+        let tenant = map.insert("tenant");
+        assert_eq!(tenant, next);
+        assert_eq!(map.lookup(removed), Lookup::Stale);
+
+        let mut map = ShortLeaseMap::new();
+        let removed = map.insert(());
+        map.remove(removed);
+        assert_eq!(map.lookup(removed), Lookup::Stale);
+
+        let mut map = Builder::with_clock(TickClock::new(0))
+            .tombstones(true)
+            .build();
+        let index = map.insert("wraps").index();
+        map.slots[index].generation = u32::MAX;
+        let before_wrap = Key::from_parts(index, u32::MAX);
+        map.advance_ticks(3);
+        assert_eq!(map.remove(before_wrap), Some("wraps"));
+        assert_eq!(map.lookup(before_wrap), Lookup::Removed { at: 3 });
+        assert_eq!(
+            map.lookup(Key::from_parts(index, u32::MAX - 1)),
+            Lookup::Stale
+        );
+        assert_eq!(map.lookup(Key::from_parts(index, 0)), Lookup::NeverIssued);
+        let after_wrap = map.insert("wrapped");
+        assert_eq!(after_wrap.generation(), 0);
+        assert_eq!(map.lookup(before_wrap), Lookup::Stale);
+    }
+
+    #[test]
+    fn integer_keys() {
+        let mut map: ShortLeaseMap<usize, u8> = Builder::new().keys().tombstones(true).build();
+        for i in 0..=255 {
+            assert_eq!(map.insert(i), i as u8);
+        }
+        assert_eq!(map.try_insert(256).unwrap_err().into_inner(), 256);
+        assert_eq!(map.remove(7), Some(7));
+        assert!(matches!(map.lookup(7), Lookup::Removed { .. }));
+        assert_eq!(map.try_insert(256), Ok(7));
+        assert_eq!(map.get(7), Some(&256));
+        assert_eq!(map.lookup(7), Lookup::Occupied(&256));
+
+        let mut map: ShortLeaseMap<(), u8, TickClock> = Builder::with_clock(TickClock::new(0))
+            .keys()
+            .quarantine_for(1)
+            .build();
+        for _ in 0..=255 {
+            map.insert(());
+        }
+        map.remove(0);
+        assert!(map.try_insert(()).is_err());
+        map.advance_ticks(1);
+        assert_eq!(map.try_insert(()), Ok(0));
+    }
+
+    #[test]
+    fn entries() {
+        let mut map = Builder::with_clock(TickClock::new(0))
+            .allocation(AllocationStrategy::Fifo)
+            .build();
+        let a = map.insert(1);
+        let b = map.insert(2);
+        let c = map.insert(3);
+        match map.entry(a) {
+            Some(Entry::Occupied(mut entry)) => {
+                assert_eq!(entry.key(), a);
+                *entry.get_mut() += 10;
+                assert_eq!(entry.replace(20), 11);
+            }
+            _ => panic!("entry should be occupied"),
+        }
+        assert_eq!(map.get(a), Some(&20));
+        assert_eq!(map.remove_if(b, |&v| v > 2), None);
+        assert_eq!(map.remove_if(b, |&v| v == 2), Some(2));
+        assert_eq!(map.replace(b, 5), Err(5));
+        assert_eq!(map.replace(c, 30), Ok(3));
+        assert_eq!(map.remove(a), Some(20));
+
+        // Reclaim b's slot even though a's was vacated more recently.
+        let Some(Entry::Vacant(entry)) = map.entry(b) else {
+            panic!("entry should be vacant");
+        };
+        let reclaimed = entry.insert_at(2);
+        assert_eq!(reclaimed.index(), b.index());
+        assert_ne!(reclaimed, b);
+        assert_eq!(map.get(b), None);
+        assert!(map.entry(b).is_none());
+        assert_eq!(map.insert(1).index(), a.index());
+        assert_eq!(map.insert(4).index(), 3);
+
+        map.advance_ticks(1);
+        let Some(Entry::Occupied(mut entry)) = map.entry(c) else {
+            panic!("entry should be occupied");
+        };
+        entry.renew();
+        assert_eq!(entry.remove(), 30);
+        assert!(matches!(map.entry(c), Some(Entry::Vacant(_))));
+        assert!(map.entry(Key::from_parts(9, 0)).is_none());
+    }
+
+    #[test]
+    fn reserve() {
+        let mut map = Builder::with_clock(TickClock::new(0))
+            .tombstones(true)
+            .build();
+        let first = map.insert((Key::from_parts(0, 0), "first"));
+        let lease = map.reserve();
+        let key = lease.key();
+        assert_eq!(lease.fill((key, "second")), key);
+        assert_eq!(map.get(key), Some(&(key, "second")));
+        assert_ne!(key, first);
+
+        let abandoned = map.reserve().key();
+        assert_eq!(map.lookup(abandoned), Lookup::Removed { at: 0 });
+        map.advance_ticks(1);
+        let lease = map.reserve();
+        assert_eq!(lease.key().index(), abandoned.index());
+        assert_ne!(lease.key(), abandoned);
+        let key = lease.key();
+        lease.fill_with_ttl((key, "third"), 5);
+        assert_eq!(map.deadline(key), Some(6));
+
+        let mut map: ShortLeaseMap<(), u8> = Builder::new().keys().build();
+        for _ in 0..=255 {
+            map.insert(());
+        }
+        assert!(map.try_reserve().is_none());
+    }
+
+    #[test]
+    fn collection_traits() {
+        let mut map: ShortLeaseMap<i32> = (0..5).collect();
+        assert_eq!(map.len(), 5);
+        let keys = map.iter().map(|(_, key)| key).collect::<Vec<_>>();
+        assert_eq!(map.iter().len(), 5);
+        assert_eq!(map.iter().next_back(), Some((&4, keys[4])));
+        assert_eq!(map[keys[2]], 2);
+        map[keys[2]] = 20;
+        assert!(map.contains_key(keys[2]));
+        map.retain(|_, value| *value % 2 == 0);
+        assert_eq!(map.len(), 3);
+        assert!(!map.contains_key(keys[1]));
+        for (value, _) in &mut map {
+            *value += 1;
+        }
+        assert_eq!((&map).into_iter().map(|(v, _)| *v).sum::<i32>(), 27);
+
+        let copy = map.clone();
+        assert_eq!(map, copy);
+        map.remove(keys[0]);
+        assert_ne!(map, copy);
+        Extend::extend(&mut map, [7]);
+        assert_ne!(map, copy);
+
+        let mut drain = map.drain();
+        assert_eq!(drain.len(), 3);
+        assert_eq!(drain.next(), Some((21, keys[2])));
+        drop(drain);
+        assert!(map.is_empty());
+        assert_eq!(map.get(keys[4]), None);
+
+        let mut iter = copy.clone().into_iter();
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.next(), Some((1, keys[0])));
+        let mut copy = copy;
+        copy.clear();
+        assert!(copy.is_empty());
+        assert_eq!(copy.insert(0).index(), keys[4].index());
+    }
+
+    #[test]
+    #[should_panic]
+    fn index_stale_key() {
+        let mut map = ShortLeaseMap::new();
+        let key = map.insert(());
+        map.remove(key);
+        map[key]
+    }
+
+    #[test]
+    fn bounded_capacity() {
+        let mut map = Builder::with_clock(TickClock::new(0))
+            .tombstones(true)
+            .max_slots(3)
+            .build();
+        let a = map.insert_with_ttl("a", 10);
+        map.advance_ticks(1);
+        let b = map.insert_with_ttl("b", 2);
+        map.advance_ticks(1);
+        let c = map.insert("c");
+        assert_eq!(map.try_insert("d").unwrap_err().into_inner(), "d");
+        assert!(map.try_reserve().is_none());
+
+        let (d, evicted) = map.insert_evicting("d").unwrap();
+        assert_eq!(evicted, Some((a, "a")));
+        assert_eq!(d.index(), a.index());
+        assert_eq!(map.lookup(a), Lookup::Stale);
+        let (e, evicted) = map
+            .insert_evicting_with("e", &mut ShortestRemainingTtl)
+            .unwrap();
+        assert_eq!(evicted, Some((b, "b")));
+        let (_, evicted) = map
+            .insert_evicting_with("f", &mut ShortestRemainingTtl)
+            .unwrap();
+        assert_eq!(evicted, Some((c, "c")));
+        let (_, evicted) = map
+            .insert_evicting_with("g", &mut Random::with_seed(7))
+            .unwrap();
+        assert!(matches!(evicted, Some((key, _)) if key == d || key == e));
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.slots.len(), 3);
+
+        map.remove(e);
+        assert_eq!(map.insert_evicting("h").unwrap().1, None);
+
+        let map: ShortLeaseMap<(), u8> = Builder::new().keys().max_slots(1000).build();
+        assert_eq!(map.max_slots, 256);
+        let mut map: ShortLeaseMap<(), Key, TickClock> = Builder::with_clock(TickClock::new(0))
+            .quarantine_for(1)
+            .max_slots(0)
+            .build();
+        assert!(map.try_insert(()).is_err());
+        map.max_slots = 1;
+        let key = map.insert(());
+        map.remove(key);
+        assert!(map.insert_evicting(()).is_err());
+
+        map.max_slots = 2;
+        let old = map.insert(());
+        let (new, evicted) = map.insert_evicting(()).unwrap();
+        assert_eq!(evicted, Some((old, ())));
+        assert_eq!(new.index(), old.index());
+        assert_eq!(map.quarantine.len(), 1);
+
+        // An integer key would be handed straight to the new value, so nothing is evicted.
+        let mut map: ShortLeaseMap<&str, u16, TickClock> = Builder::with_clock(TickClock::new(0))
+            .keys()
+            .quarantine_for(5)
+            .max_slots(1)
+            .build();
+        let old = map.insert("old");
+        assert_eq!(map.insert_evicting("new").unwrap_err().into_inner(), "new");
+        assert_eq!(map.get(old), Some(&"old"));
+        map.remove(old);
+        map.advance_ticks(5);
+        assert_eq!(map.insert_evicting("new").unwrap().1, None);
+    }
+
+    #[test]
+    fn system_clock() {
+        let mut map = ShortLeaseMap::new();
+        let key = map.insert_with_deadline((), Instant::now() + Duration::from_secs(60));
+        assert_eq!(map.dump_expired(), 0);
+        assert_eq!(map.remove(key), Some(()));
+        let forever = map.insert_with_ttl((), Duration::MAX);
+        assert_eq!(map.remaining(forever, Duration::MAX), Some(Duration::MAX));
+        assert_eq!(map.deadline(forever), None);
+        assert!(map.renew(forever));
+        assert_eq!(map.deadline(forever), None);
     }
 }
@@ -0,0 +1,232 @@
+use std::{
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{expect_room, CapacityError, Clock, Key, ShortLeaseMap, SystemClock};
+
+/// A [`ShortLeaseMap`] shared behind an `Arc<Mutex<..>>`, with a background thread which
+/// periodically evicts expired values, so nobody has to remember to call
+/// [`dump_old_values`](ShortLeaseMap::dump_old_values).
+///
+/// Evicted values are handed to a callback, or sent down a channel. Handles can be cloned, and
+/// the thread shuts down when the last one is dropped.
+#[derive(Debug)]
+pub struct ReapedShortLeaseMap<T, C: Clock = SystemClock> {
+    map: Arc<Mutex<ShortLeaseMap<T, Key, C>>>,
+    reaper: Arc<Reaper>,
+}
+
+/// Stops the reaper thread when the last handle lets go of it.
+#[derive(Debug)]
+struct Reaper {
+    /// Dropping this wakes the thread and tells it to stop.
+    shutdown: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<T, C> ReapedShortLeaseMap<T, C>
+where
+    T: Send + 'static,
+    C: Clock + Send + 'static,
+    C::Instant: Send,
+    C::Duration: Send,
+{
+    /// Takes ownership of `map` and starts a thread which, every `every`, evicts values whose own
+    /// deadline has been reached, as well as values older than `max_age` if one is given. Each
+    /// evicted value is passed to `on_evict` along with its key.
+    ///
+    /// `on_evict` is called without the map locked, so it may use the map. It should not keep a
+    /// handle to the map though, or the thread will keep itself running.
+    pub fn spawn(
+        map: ShortLeaseMap<T, Key, C>,
+        max_age: Option<C::Duration>,
+        every: Duration,
+        mut on_evict: impl FnMut(Key, T) + Send + 'static,
+    ) -> Self {
+        let map = Arc::new(Mutex::new(map));
+        let (shutdown, shutdown_rx) = mpsc::channel::<()>();
+        let thread = {
+            let map = Arc::clone(&map);
+            thread::Builder::new()
+                .name("short-lease-map-reaper".into())
+                .spawn(move || {
+                    while let Err(RecvTimeoutError::Timeout) = shutdown_rx.recv_timeout(every) {
+                        let evicted = {
+                            let mut map = map.lock().unwrap();
+                            let mut evicted = map.drain_overdue().collect::<Vec<_>>();
+                            if let Some(max_age) = max_age {
+                                evicted.extend(map.drain_expired(max_age));
+                            }
+                            evicted
+                        };
+                        for (key, t, _) in evicted {
+                            on_evict(key, t);
+                        }
+                    }
+                })
+                .expect("failed to spawn reaper thread")
+        };
+        Self {
+            map,
+            reaper: Arc::new(Reaper {
+                shutdown: Some(shutdown),
+                thread: Some(thread),
+            }),
+        }
+    }
+
+    /// Like [`spawn`](Self::spawn), but evicted values are sent down the returned channel.
+    pub fn spawn_with_channel(
+        map: ShortLeaseMap<T, Key, C>,
+        max_age: Option<C::Duration>,
+        every: Duration,
+    ) -> (Self, Receiver<(Key, T)>) {
+        let (tx, rx) = mpsc::channel();
+        let this = Self::spawn(map, max_age, every, move |key, t| {
+            // Nobody listening is not our problem, the values are evicted either way.
+            let _ = tx.send((key, t));
+        });
+        (this, rx)
+    }
+}
+
+impl<T, C: Clock> ReapedShortLeaseMap<T, C> {
+    /// Locks the map, for anything not covered by the shortcuts on this type. The reaper waits
+    /// while the guard is held.
+    pub fn lock(&self) -> MutexGuard<'_, ShortLeaseMap<T, Key, C>> {
+        self.map.lock().unwrap()
+    }
+
+    /// The shared map itself.
+    pub fn shared(&self) -> &Arc<Mutex<ShortLeaseMap<T, Key, C>>> {
+        &self.map
+    }
+
+    /// Adds a value to the map. See [`ShortLeaseMap::insert`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map is full. Use [`try_insert`](Self::try_insert) to handle that instead.
+    pub fn insert(&self, t: T) -> Key {
+        expect_room(self.try_insert(t))
+    }
+
+    /// Adds a value to the map, but if the map is full, hands the value back instead of
+    /// panicking. See [`ShortLeaseMap::try_insert`].
+    pub fn try_insert(&self, t: T) -> Result<Key, CapacityError<T>> {
+        self.lock().try_insert(t)
+    }
+
+    /// Adds a value to the map which expires once `ttl` has passed. See
+    /// [`ShortLeaseMap::insert_with_ttl`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map is full.
+    pub fn insert_with_ttl(&self, t: T, ttl: C::Duration) -> Key {
+        // Panic once the lock is released, so the reaper and other handles carry on.
+        let inserted = self.lock().try_insert_with_ttl(t, ttl);
+        expect_room(inserted)
+    }
+
+    /// Clones the value for this key. Returns `None` if the key is stale, which includes values
+    /// the reaper has evicted.
+    pub fn get(&self, key: Key) -> Option<T>
+    where
+        T: Clone,
+    {
+        self.lock().get(key).cloned()
+    }
+
+    /// Removes the value with this key. See [`ShortLeaseMap::remove`].
+    pub fn remove(&self, key: Key) -> Option<T> {
+        self.lock().remove(key)
+    }
+}
+
+impl<T, C: Clock> Clone for ReapedShortLeaseMap<T, C> {
+    fn clone(&self) -> Self {
+        Self {
+            map: Arc::clone(&self.map),
+            reaper: Arc::clone(&self.reaper),
+        }
+    }
+}
+
+impl Drop for Reaper {
+    fn drop(&mut self) {
+        drop(self.shutdown.take());
+        if let Some(thread) = self.thread.take() {
+            // The last handle may be dropped by the reaper itself, from within the callback.
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Builder, ManualClock};
+
+    #[test]
+    fn reaps_expired_values() {
+        let clock = ManualClock::new();
+        let (map, evicted) = ReapedShortLeaseMap::spawn_with_channel(
+            ShortLeaseMap::with_clock(clock.clone()),
+            Some(Duration::from_secs(10)),
+            Duration::from_millis(1),
+        );
+        let old = map.insert("old");
+        let short = map.insert_with_ttl("short", Duration::from_secs(1));
+        let young = map.clone().insert("young");
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(evicted.recv().unwrap(), (short, "short"));
+        assert_eq!(map.get(short), None);
+        clock.advance(Duration::from_secs(9));
+        assert_eq!(evicted.recv().unwrap().0, old);
+        assert_eq!(evicted.recv().unwrap().0, young);
+        assert!(map.lock().oldest().is_none());
+    }
+
+    #[test]
+    fn stops_with_last_handle() {
+        let (map, evicted) = ReapedShortLeaseMap::spawn_with_channel(
+            ShortLeaseMap::new(),
+            None,
+            Duration::from_secs(3600),
+        );
+        let other = map.clone();
+        drop(map);
+        other.insert(());
+        drop(other);
+        // The thread dropped the callback, and with it the sender, before the handle returned.
+        assert!(evicted.try_recv().is_err());
+        assert_eq!(evicted.recv(), Err(mpsc::RecvError));
+    }
+
+    #[test]
+    fn full() {
+        let map = ReapedShortLeaseMap::spawn(
+            Builder::new().max_slots(1).build(),
+            None,
+            Duration::from_millis(1),
+            |_, _: u32| {},
+        );
+        let key = map.insert(1);
+        assert_eq!(map.try_insert(2).unwrap_err().into_inner(), 2);
+        let insert = || map.insert(3);
+        assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(insert)).is_err());
+        let insert_with_ttl = || map.insert_with_ttl(3, Duration::MAX);
+        assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(insert_with_ttl)).is_err());
+        assert!(!map.shared().is_poisoned());
+        assert_eq!(map.remove(key), Some(1));
+        assert!(map.try_insert(4).is_ok());
+    }
+}
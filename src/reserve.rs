@@ -0,0 +1,202 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::{Clock, Key, LeaseKey, ReapedShortLeaseMap, ShortLeaseMap, SystemClock};
+
+/// A slot claimed by [`ShortLeaseMap::reserve`] which doesn't have a value yet. Its key is known
+/// up front, so it can go inside the value.
+///
+/// Dropping the lease without [filling](Self::fill) it releases the slot, and the key becomes
+/// stale, as if its value had been removed.
+#[derive(Debug)]
+pub struct VacantLease<'a, T, K: LeaseKey = Key, C: Clock = SystemClock> {
+    map: &'a mut ShortLeaseMap<T, K, C>,
+    index: usize,
+    /// Whether dropping the lease releases the slot.
+    armed: bool,
+}
+
+impl<'a, T, K: LeaseKey, C: Clock> VacantLease<'a, T, K, C> {
+    pub(crate) fn new(map: &'a mut ShortLeaseMap<T, K, C>, index: usize) -> Self {
+        Self {
+            map,
+            index,
+            armed: true,
+        }
+    }
+
+    /// The key the value will have once the lease is filled.
+    pub fn key(&self) -> K {
+        self.map.key_at(self.index)
+    }
+
+    /// Puts the value in the reserved slot, returning its key. The lease starts now, not when the
+    /// slot was reserved.
+    pub fn fill(mut self, t: T) -> K {
+        self.armed = false;
+        self.map.fill_reserved(self.index, t, None)
+    }
+
+    /// Puts the value in the reserved slot like [`fill`](Self::fill), with a value which expires
+    /// once `ttl` has passed. If that's further off than the clock can represent, the value never
+    /// expires on its own.
+    pub fn fill_with_ttl(mut self, t: T, ttl: C::Duration) -> K {
+        self.armed = false;
+        self.map.fill_reserved(self.index, t, Some(ttl))
+    }
+}
+
+impl<T, K: LeaseKey, C: Clock> Drop for VacantLease<'_, T, K, C> {
+    fn drop(&mut self) {
+        if self.armed {
+            self.map.release_reserved(self.index);
+        }
+    }
+}
+
+/// A slot claimed in a shared [`ShortLeaseMap`] which doesn't have a value yet, like a
+/// [`VacantLease`], except the map is only locked while reserving and filling the slot.
+///
+/// Others can use the map in the meantime, but the slot isn't handed out to them, and sweeps pass
+/// over it. Dropping the lease without [filling](Self::fill) it releases the slot, and the key
+/// becomes stale, as if its value had been removed.
+#[derive(Debug)]
+pub struct SharedVacantLease<T, C: Clock = SystemClock> {
+    map: Arc<Mutex<ShortLeaseMap<T, Key, C>>>,
+    key: Key,
+    /// Whether dropping the lease releases the slot.
+    armed: bool,
+}
+
+impl<T, C: Clock> SharedVacantLease<T, C> {
+    /// Claims a slot in the shared map. See [`ShortLeaseMap::reserve`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map is [full](ShortLeaseMap::try_insert). Use
+    /// [`try_reserve`](Self::try_reserve) to handle that instead.
+    pub fn reserve(map: &Arc<Mutex<ShortLeaseMap<T, Key, C>>>) -> Self {
+        Self::try_reserve(map).expect("ShortLeaseMap is full")
+    }
+
+    /// Claims a slot like [`reserve`](Self::reserve), but returns `None` if the map is full.
+    pub fn try_reserve(map: &Arc<Mutex<ShortLeaseMap<T, Key, C>>>) -> Option<Self> {
+        let key = {
+            let mut locked = map.lock().unwrap();
+            let index = locked.reserve_slot()?;
+            locked.key_at(index)
+        };
+        Some(Self {
+            map: Arc::clone(map),
+            key,
+            armed: true,
+        })
+    }
+
+    /// The key the value will have once the lease is filled.
+    pub fn key(&self) -> Key {
+        self.key
+    }
+
+    /// Puts the value in the reserved slot, returning its key. The lease starts now, not when the
+    /// slot was reserved.
+    pub fn fill(mut self, t: T) -> Key {
+        self.armed = false;
+        self.lock().fill_reserved(self.key.index(), t, None)
+    }
+
+    /// Puts the value in the reserved slot like [`fill`](Self::fill), with a value which expires
+    /// once `ttl` has passed. If that's further off than the clock can represent, the value never
+    /// expires on its own.
+    pub fn fill_with_ttl(mut self, t: T, ttl: C::Duration) -> Key {
+        self.armed = false;
+        self.lock().fill_reserved(self.key.index(), t, Some(ttl))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ShortLeaseMap<T, Key, C>> {
+        self.map.lock().unwrap()
+    }
+}
+
+impl<T, C: Clock> Drop for SharedVacantLease<T, C> {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        // Like `LeaseGuard`, leave a poisoned map alone.
+        if let Ok(mut map) = self.map.lock() {
+            map.release_reserved(self.key.index());
+        }
+    }
+}
+
+impl<T, C: Clock> ReapedShortLeaseMap<T, C> {
+    /// Claims a slot before its value exists, without keeping the map locked until it's filled.
+    /// See [`ShortLeaseMap::reserve`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map is [full](ShortLeaseMap::try_insert).
+    pub fn reserve(&self) -> SharedVacantLease<T, C> {
+        SharedVacantLease::reserve(self.shared())
+    }
+
+    /// Claims a slot like [`reserve`](Self::reserve), but returns `None` if the map is full.
+    pub fn try_reserve(&self) -> Option<SharedVacantLease<T, C>> {
+        SharedVacantLease::try_reserve(self.shared())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{Builder, Lookup, TickClock};
+
+    #[test]
+    fn shared() {
+        let map = Arc::new(Mutex::new(
+            Builder::with_clock(TickClock::new(0))
+                .tombstones(true)
+                .build(),
+        ));
+        let lease = SharedVacantLease::reserve(&map);
+        let key = lease.key();
+        assert_eq!(map.lock().unwrap().lookup(key), Lookup::Reserved);
+        let other = map.lock().unwrap().insert((Key::from_parts(0, 0), "other"));
+        assert_ne!(other.index(), key.index());
+        map.lock().unwrap().advance_ticks(5);
+        assert_eq!(map.lock().unwrap().dump_older_than_ticks(1), 1);
+        assert!(map.lock().unwrap().entry(key).is_none());
+        assert_eq!(lease.fill((key, "filled")), key);
+        assert_eq!(map.lock().unwrap().age(key), Some(0));
+
+        let abandoned = SharedVacantLease::reserve(&map).key();
+        assert_eq!(
+            map.lock().unwrap().lookup(abandoned),
+            Lookup::Removed { at: 5 }
+        );
+
+        let map = Arc::new(Mutex::new(Builder::new().max_slots(1).build()));
+        map.lock().unwrap().insert(());
+        assert!(SharedVacantLease::try_reserve(&map).is_none());
+        assert!(std::panic::catch_unwind(|| SharedVacantLease::reserve(&map)).is_err());
+        assert!(!map.is_poisoned());
+    }
+
+    #[test]
+    fn reaped_map() {
+        let map = ReapedShortLeaseMap::spawn(
+            ShortLeaseMap::new(),
+            None,
+            Duration::from_secs(3600),
+            |_, _: Key| {},
+        );
+        let lease = map.reserve();
+        let key = lease.key();
+        let other = map.insert(key);
+        assert_ne!(other, key);
+        assert_eq!(lease.fill(key), key);
+        assert_eq!(map.get(key), Some(key));
+    }
+}
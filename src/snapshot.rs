@@ -0,0 +1,226 @@
+use std::cmp::Reverse;
+
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{Builder, Clock, LeaseKey, Occupant, ShortLeaseMap, Slot};
+
+/// The serialized form of a map. Times are stored relative to when the snapshot was taken, so
+/// they can be rebased onto whatever clock the map is restored with.
+#[derive(Serialize, Deserialize)]
+struct Snapshot<T, D> {
+    /// The generation of every slot, occupied or not, so keys stay valid across a round trip.
+    generations: Vec<u32>,
+    /// The occupied slots, in check in order.
+    leases: Vec<Lease<T, D>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Lease<T, D> {
+    index: usize,
+    value: T,
+    /// How long ago the value was inserted, or last renewed.
+    age: D,
+    /// How long until the value's own deadline, or zero if it has already passed.
+    deadline_in: Option<D>,
+    ttl: Option<D>,
+}
+
+/// Serializes the slots, their generations, and each value's age, rather than the instant it was
+/// inserted. The map's settings, such as its clock and [allocation strategy](Builder::allocation),
+/// aren't included, and neither are [tombstones](Builder::tombstones). [Reserved](ShortLeaseMap::reserve)
+/// slots come back vacant, and the keys of their leases come back stale.
+impl<T: Serialize, K: LeaseKey, C: Clock> Serialize for ShortLeaseMap<T, K, C>
+where
+    C::Duration: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let now = self.clock.now();
+        let mut leases = Vec::new();
+        let mut next = self.head;
+        while let Some(index) = next {
+            let occupant = self.occupant(index);
+            leases.push(Lease {
+                index,
+                value: &occupant.value,
+                age: C::duration_since(now, occupant.inserted_at),
+                deadline_in: occupant
+                    .deadline
+                    .map(|deadline| C::duration_since(deadline, now)),
+                ttl: occupant.ttl,
+            });
+            next = occupant.next;
+        }
+        Snapshot {
+            // A reserved slot's key is already out, so it has to be stale once restored.
+            generations: self
+                .slots
+                .iter()
+                .map(|s| {
+                    if s.reserved {
+                        s.generation.wrapping_add(1)
+                    } else {
+                        s.generation
+                    }
+                })
+                .collect(),
+            leases,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Restores a map with the default settings, rebasing each value's age onto a default clock. Use
+/// [`Builder::build_from`] to choose the settings and clock.
+impl<'de, T: Deserialize<'de>, K: LeaseKey, C: Clock + Default> Deserialize<'de>
+    for ShortLeaseMap<T, K, C>
+where
+    C::Duration: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Builder::with_clock(C::default())
+            .keys()
+            .build_from(deserializer)
+    }
+}
+
+impl<K: LeaseKey, C: Clock> Builder<K, C> {
+    /// Builds the map from a snapshot made by serializing a [`ShortLeaseMap`]. Keys handed out
+    /// before the snapshot was taken stay valid.
+    ///
+    /// Each value keeps the age it had when the snapshot was taken, measured back from the
+    /// current time of this builder's clock, and values with their own deadline keep the time
+    /// they had left. If an age reaches back further than the clock can represent, the value is
+    /// treated as inserted just now. Vacant slots go on the free list, or into a fresh
+    /// [quarantine](Self::quarantine_for) if this builder sets one.
+    pub fn build_from<'de, T, D>(self, deserializer: D) -> Result<ShortLeaseMap<T, K, C>, D::Error>
+    where
+        T: Deserialize<'de>,
+        C::Duration: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let Snapshot {
+            generations,
+            mut leases,
+        } = Snapshot::<T, C::Duration>::deserialize(deserializer)?;
+        let mut map = self.build();
+        if generations.len() > map.max_slots {
+            return Err(D::Error::custom(format_args!(
+                "{} slots won't fit in a map with at most {}",
+                generations.len(),
+                map.max_slots
+            )));
+        }
+        let now = map.clock.now();
+        map.slots
+            .extend(generations.into_iter().map(|generation| Slot {
+                generation,
+                occupant: None,
+                reserved: false,
+                tombstone: None,
+            }));
+        // Check in order must run from oldest to youngest for sweeps to stop early.
+        leases.sort_by_key(|lease| Reverse(lease.age));
+        for lease in leases {
+            let index = lease.index;
+            let slot = map.slots.get_mut(index).ok_or_else(|| {
+                D::Error::custom(format_args!("lease for slot {index}, which doesn't exist"))
+            })?;
+            if slot.occupant.is_some() {
+                return Err(D::Error::custom(format_args!(
+                    "more than one lease for slot {index}"
+                )));
+            }
+            slot.occupant = Some(Occupant {
+                value: lease.value,
+                inserted_at: C::checked_sub(now, lease.age).unwrap_or(now),
+                deadline: None,
+                ttl: lease.ttl,
+                prev: None,
+                next: None,
+            });
+            map.len += 1;
+            map.link_back(index);
+            map.set_deadline(
+                index,
+                lease.deadline_in.and_then(|d| C::checked_add(now, d)),
+            );
+        }
+        let quarantined = map.has_quarantine();
+        for index in 0..map.slots.len() {
+            if map.slots[index].occupant.is_some() {
+                continue;
+            }
+            if quarantined {
+                map.quarantine.push_back((index, now, 0));
+            } else {
+                map.free.push(index);
+            }
+        }
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{Key, Lookup, SharedVacantLease, TickClock};
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut map = Builder::with_clock(TickClock::new(10)).build();
+        let gone = map.insert("gone");
+        let old = map.insert("old");
+        map.advance_ticks(5);
+        let young = map.insert_with_ttl("young", 20);
+        map.remove(gone);
+        let json = serde_json::to_string(&map).unwrap();
+
+        let mut restored: ShortLeaseMap<String, Key, TickClock> =
+            Builder::with_clock(TickClock::new(100))
+                .build_from(&mut serde_json::Deserializer::from_str(&json))
+                .unwrap();
+        assert_eq!(restored.get(old).map(String::as_str), Some("old"));
+        assert_eq!(restored.len(), 2);
+        assert_eq!(restored.age(old), Some(5));
+        assert_eq!(restored.age(young), Some(0));
+        assert_eq!(restored.deadline(young), Some(120));
+        assert_eq!(restored.lookup(gone), Lookup::Stale);
+        assert_eq!(restored.oldest().map(|(key, ..)| key), Some(old));
+        let reused = restored.insert("new".to_owned());
+        assert_eq!(reused.index(), gone.index());
+        assert_ne!(reused, gone);
+        assert_eq!(restored.dump_old_values(3), 1);
+        assert_eq!(restored.get(old), None);
+
+        let restored: ShortLeaseMap<String, Key, TickClock> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.age(old), Some(0));
+    }
+
+    #[test]
+    fn reserved_keys_restore_stale() {
+        let map = Arc::new(Mutex::new(Builder::with_clock(TickClock::new(0)).build()));
+        let lease = SharedVacantLease::reserve(&map);
+        let reserved = lease.key();
+        let json = serde_json::to_string(&*map.lock().unwrap()).unwrap();
+        lease.fill(1);
+
+        let mut restored: ShortLeaseMap<u32, Key, TickClock> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.lookup(reserved), Lookup::Stale);
+        let key = restored.insert(7);
+        assert_eq!(key.index(), reserved.index());
+        assert_ne!(key, reserved);
+        assert_eq!(restored.get(reserved), None);
+    }
+
+    #[test]
+    fn rejects_bad_snapshots() {
+        let json = r#"{"generations":[0],"leases":[{"index":1,"value":1,"age":0,"deadline_in":null,"ttl":null}]}"#;
+        assert!(serde_json::from_str::<ShortLeaseMap<u32, Key, TickClock>>(json).is_err());
+        let json = format!(r#"{{"generations":{:?},"leases":[]}}"#, [0; 257]);
+        assert!(serde_json::from_str::<ShortLeaseMap<u32, u8, TickClock>>(&json).is_err());
+        assert!(serde_json::from_str::<ShortLeaseMap<u32, u16, TickClock>>(&json).is_ok());
+    }
+}
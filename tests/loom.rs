@@ -0,0 +1,74 @@
+#![cfg(loom)]
+//! Run with `RUSTFLAGS="--cfg loom" cargo test --release --test loom`.
+
+use std::time::Duration;
+
+use loom::{sync::Arc, thread};
+use short_lease_map::{ConcurrentShortLeaseMap, ManualClock};
+
+#[test]
+fn concurrent_inserts_get_distinct_keys() {
+    loom::model(|| {
+        let map = Arc::new(ConcurrentShortLeaseMap::with_shards(2));
+        let other = {
+            let map = Arc::clone(&map);
+            thread::spawn(move || map.insert(1))
+        };
+        let mine = map.insert(0);
+        let other = other.join().unwrap();
+        assert_ne!(mine, other);
+        assert_eq!(map.get(mine), Some(0));
+        assert_eq!(map.get(other), Some(1));
+    });
+}
+
+#[test]
+fn concurrent_inserts_into_one_shard() {
+    loom::model(|| {
+        let map = Arc::new(ConcurrentShortLeaseMap::with_shards(1));
+        let other = {
+            let map = Arc::clone(&map);
+            thread::spawn(move || map.insert(1))
+        };
+        let mine = map.insert(0);
+        let other = other.join().unwrap();
+        assert_ne!(mine.key().index(), other.key().index());
+        assert_eq!(map.remove(mine), Some(0));
+        assert_eq!(map.remove(other), Some(1));
+    });
+}
+
+#[test]
+fn value_is_removed_once() {
+    loom::model(|| {
+        let map = Arc::new(ConcurrentShortLeaseMap::with_shards(2));
+        let key = map.insert(());
+        let other = {
+            let map = Arc::clone(&map);
+            thread::spawn(move || map.remove(key))
+        };
+        let mine = map.remove(key);
+        let other = other.join().unwrap();
+        assert!(mine.is_some() != other.is_some());
+    });
+}
+
+#[test]
+fn sweep_and_remove_never_both_take_a_value() {
+    loom::model(|| {
+        let clock = ManualClock::new();
+        let map = Arc::new(ConcurrentShortLeaseMap::with_shards_and_clock(
+            2,
+            clock.clone(),
+        ));
+        let key = map.insert(());
+        clock.advance(Duration::from_secs(1));
+        let sweeper = {
+            let map = Arc::clone(&map);
+            thread::spawn(move || map.drain_expired(Duration::ZERO))
+        };
+        let removed = map.remove(key);
+        let swept = sweeper.join().unwrap();
+        assert_eq!(removed.is_some() as usize + swept.len(), 1);
+    });
+}